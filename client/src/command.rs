@@ -0,0 +1,129 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use protocol::SearchQuery;
+
+use crate::emoji;
+
+/// Slash commands typed into the chat input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Delete your most recent message.
+    Delete,
+    /// React to the selected message, or the newest one, with an emoji.
+    React(String),
+    /// Send a direct message to a single user.
+    Direct { to: String, body: String },
+    /// Search the chat history on the server.
+    Search(SearchQuery),
+}
+
+/// Every command name, used for the help text.
+pub const COMMAND_NAMES: &[&str] = &["delete", "msg", "react", "search"];
+
+impl Command {
+    /// Parses `input` as a command. Returns `None` if the input isn't a command at all,
+    /// i.e. doesn't start with `/`.
+    pub fn parse(input: &str) -> Option<Result<Self>> {
+        let input = input.strip_prefix('/')?;
+        let (name, args) = input.split_once(' ').unwrap_or((input, ""));
+        Some(match name {
+            "delete" => Ok(Command::Delete),
+            "react" => emoji::parse_reaction(args).map(Command::React),
+            "msg" => match args.trim_start().split_once(' ') {
+                Some((to, body)) if !body.trim().is_empty() => Ok(Command::Direct {
+                    to: to.to_string(),
+                    body: body.to_string(),
+                }),
+                _ => Err(anyhow!("Usage: /msg <user> <message>")),
+            },
+            "search" => parse_search(args).map(Command::Search),
+            _ => Self::unknown(name),
+        })
+    }
+
+    fn unknown(name: &str) -> Result<Self> {
+        bail!(
+            "Unknown command /{}, available commands: /{}",
+            name,
+            COMMAND_NAMES.join(", /")
+        )
+    }
+}
+
+/// Parses `/search` arguments: plain words to look for, plus `from:<user>`,
+/// `in:<lobby or user>`, `after:<yyyy-mm-dd>` and `before:<yyyy-mm-dd>` filters.
+fn parse_search(args: &str) -> Result<SearchQuery> {
+    let mut query = SearchQuery::default();
+    let mut words = Vec::new();
+    for word in args.split_whitespace() {
+        match word.split_once(':') {
+            Some(("from", author)) => query.author = Some(author.to_string()),
+            Some(("in", conversation)) => query.conversation = Some(conversation.to_string()),
+            Some(("after", date)) => query.after = Some(parse_date(date)?),
+            Some(("before", date)) => query.before = Some(parse_date(date)?),
+            _ => words.push(word),
+        }
+    }
+    if !words.is_empty() {
+        query.text = Some(words.join(" "));
+    }
+    if query.text.is_none()
+        && query.author.is_none()
+        && query.conversation.is_none()
+        && query.after.is_none()
+        && query.before.is_none()
+    {
+        bail!(
+            "Usage: /search <words> [from:<user>] [in:<lobby|user>] [after:<date>] [before:<date>]"
+        );
+    }
+    Ok(query)
+}
+
+/// Start of a `yyyy-mm-dd` day in local time.
+fn parse_date(date: &str) -> Result<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date {date}, expected yyyy-mm-dd"))?;
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .map(|midnight| midnight.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("Invalid date {date}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(input: &str) -> SearchQuery {
+        match Command::parse(input) {
+            Some(Ok(Command::Search(query))) => query,
+            other => panic!("{input} parsed as {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_search_words_and_filters() {
+        let query = search("/search lunch from:bob in:lobby noon");
+        assert_eq!(query.text.as_deref(), Some("lunch noon"));
+        assert_eq!(query.author.as_deref(), Some("bob"));
+        assert_eq!(query.conversation.as_deref(), Some("lobby"));
+        assert!(query.after.is_none() && query.before.is_none());
+    }
+
+    #[test]
+    fn parses_search_dates() {
+        let query = search("/search after:2024-01-01 before:2024-02-01");
+        assert!(query.text.is_none());
+        assert!(query.after.unwrap() < query.before.unwrap());
+        assert!(matches!(
+            Command::parse("/search after:yesterday"),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn search_needs_something_to_look_for() {
+        assert!(matches!(Command::parse("/search"), Some(Err(_))));
+        assert!(matches!(Command::parse("/search   "), Some(Err(_))));
+    }
+}
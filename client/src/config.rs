@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use serde::Deserialize;
+
+/// User configuration, read from `$XDG_CONFIG_HOME/chat_tea/config.toml`.
+/// Every field is optional in the file and falls back to its default.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// `chrono` format string for the local time shown before each chat line.
+    /// An empty string hides the timestamp.
+    pub timestamp_format: String,
+    /// Store read positions on the server, so they survive restarts and are shared between clients.
+    pub sync_read_markers: bool,
+    /// Most log lines kept for the Logs tab, older lines are dropped.
+    pub log_buffer_size: usize,
+    /// Also write logs to a daily rotated file in the state directory.
+    pub log_to_file: bool,
+    pub render_mode: RenderMode,
+    /// Most lines of text the chat input grows to before scrolling.
+    pub input_max_height: u16,
+    /// Edit the chat input with vi keys in normal mode.
+    pub vi_mode: bool,
+    /// A built-in theme or one from the `themes` directory, see `Theme::load`.
+    pub theme: String,
+    /// Words that highlight a message like a mention of your name does, ignoring case.
+    pub highlight_words: Vec<String>,
+    pub notification: Notification,
+    /// Show `*bold*`, `_italic_`, `code` and quotes in messages formatted instead of as typed.
+    pub markup: bool,
+    /// Make links clickable with OSC 8 hyperlinks. On by default in terminals known to
+    /// support them.
+    pub hyperlinks: bool,
+    /// Program, and any arguments before the link, that opens links from the link picker.
+    pub open_command: String,
+}
+
+/// When the terminal is redrawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderMode {
+    /// Only redraw after something changed, at most at the frame rate.
+    OnChange,
+    /// Redraw at the frame rate whether or not anything changed.
+    FixedRate,
+}
+
+/// How a mention is announced while the terminal doesn't have focus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Notification {
+    Off,
+    /// Ring the terminal bell.
+    Bell,
+    /// A desktop notification through OSC 9, as supported by iTerm2, kitty and WezTerm.
+    Osc9,
+    /// A desktop notification through OSC 777, as supported by foot, urxvt and VTE terminals.
+    Osc777,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            timestamp_format: "%H:%M".to_string(),
+            sync_read_markers: true,
+            log_buffer_size: 1000,
+            log_to_file: true,
+            render_mode: RenderMode::OnChange,
+            input_max_height: 5,
+            vi_mode: false,
+            theme: "dark".to_string(),
+            highlight_words: Vec::new(),
+            notification: Notification::Bell,
+            markup: true,
+            hyperlinks: terminal_supports_hyperlinks(),
+            open_command: default_open_command().to_string(),
+        }
+    }
+}
+
+/// Whether the terminal is one known to support OSC 8 hyperlinks, going by the variables
+/// it sets. Others may print the escape sequences as text.
+fn terminal_supports_hyperlinks() -> bool {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    if [
+        "WT_SESSION",
+        "KITTY_WINDOW_ID",
+        "KONSOLE_VERSION",
+        "DOMTERM",
+    ]
+    .iter()
+    .any(|name| std::env::var_os(name).is_some())
+    {
+        return true;
+    }
+    if var("VTE_VERSION")
+        .parse::<u32>()
+        .is_ok_and(|version| version >= 5000)
+    {
+        return true;
+    }
+    matches!(
+        var("TERM_PROGRAM").as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper"
+    ) || matches!(
+        var("TERM").as_str(),
+        "xterm-kitty" | "xterm-ghostty" | "alacritty" | "foot" | "wezterm"
+    )
+}
+
+fn default_open_command() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config: Self = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if StrftimeItems::new(&self.timestamp_format).any(|item| item == Item::Error) {
+            bail!("Invalid timestamp_format: {:?}", self.timestamp_format);
+        }
+        if self.log_buffer_size == 0 {
+            bail!("log_buffer_size must be at least 1");
+        }
+        if self.input_max_height == 0 {
+            bail!("input_max_height must be at least 1");
+        }
+        Ok(())
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("config.toml"))
+    }
+}
+
+/// Directory holding every chat_tea configuration file.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("chat_tea"))
+}
+
+/// Directory for files the client writes, like logs. `$XDG_STATE_HOME/chat_tea` where
+/// the platform has one, the local data directory otherwise.
+pub fn state_dir() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("chat_tea"))
+}
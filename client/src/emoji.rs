@@ -0,0 +1,76 @@
+use anyhow::{bail, Result};
+use protocol::MAX_REACTION_LEN;
+
+/// Shortcodes accepted in place of an emoji, with or without the surrounding colons.
+pub const SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("thumbsup", "👍"),
+    ("-1", "👎"),
+    ("thumbsdown", "👎"),
+    ("heart", "❤️"),
+    ("joy", "😂"),
+    ("smile", "😄"),
+    ("wink", "😉"),
+    ("cry", "😢"),
+    ("thinking", "🤔"),
+    ("eyes", "👀"),
+    ("tada", "🎉"),
+    ("fire", "🔥"),
+    ("rocket", "🚀"),
+    ("clap", "👏"),
+    ("pray", "🙏"),
+    ("wave", "👋"),
+    ("ok_hand", "👌"),
+    ("100", "💯"),
+    ("check", "✅"),
+    ("x", "❌"),
+];
+
+/// Turns user input into the emoji to react with, resolving shortcodes like `:tada:`.
+pub fn parse_reaction(input: &str) -> Result<String> {
+    let input = input.trim();
+    if input.is_empty() {
+        bail!("Usage: /react <emoji or :shortcode:>");
+    }
+    let shortcode = input
+        .strip_prefix(':')
+        .and_then(|code| code.strip_suffix(':'))
+        .unwrap_or(input);
+    if let Some((_, emoji)) = SHORTCODES.iter().find(|(code, _)| *code == shortcode) {
+        return Ok(emoji.to_string());
+    }
+    if input.len() > MAX_REACTION_LEN
+        || input
+            .chars()
+            .any(|c| c.is_alphanumeric() || c.is_whitespace())
+    {
+        bail!("Unknown emoji {}", input);
+    }
+    Ok(input.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_shortcodes() {
+        assert_eq!(parse_reaction(":tada:").unwrap(), "🎉");
+        assert_eq!(parse_reaction(" tada ").unwrap(), "🎉");
+        assert!(parse_reaction(":nope:").is_err());
+    }
+
+    #[test]
+    fn takes_emoji_as_they_are() {
+        assert_eq!(parse_reaction("🦀").unwrap(), "🦀");
+        assert_eq!(parse_reaction("👍🏽").unwrap(), "👍🏽");
+    }
+
+    #[test]
+    fn rejects_words_and_long_input() {
+        assert!(parse_reaction("").is_err());
+        assert!(parse_reaction("lol").is_err());
+        assert!(parse_reaction("🎉 🎉").is_err());
+        assert!(parse_reaction(&"🎉".repeat(MAX_REACTION_LEN)).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+use crate::{config_dir, InputMode};
+
+/// Everything a key can be bound to. Each action belongs to one input mode or popup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    // Normal mode
+    Quit,
+    StartEditing,
+    SwitchTab,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    ToggleFilter,
+    /// Clears the search, closes the thread or clears the selection, whichever applies first.
+    Cancel,
+    CycleLogLevel,
+    DumpLogs,
+    SelectNext,
+    SelectPrevious,
+    EditMessage,
+    Reply,
+    React,
+    ToggleThread,
+    /// Shows the selected message as it was typed, without formatting, or formatted again.
+    ToggleRaw,
+    /// Opens the list of recent links, to copy or open one.
+    PickLink,
+    // Editing mode
+    Send,
+    Newline,
+    StopEditing,
+    HistoryOlder,
+    HistoryNewer,
+    SearchHistory,
+    Complete,
+    CompleteBack,
+    // Typing a search query
+    ConfirmSearch,
+    CancelSearch,
+    // Reverse search of the input history
+    AcceptMatch,
+    OlderMatch,
+    CancelReverseSearch,
+    // Search results popup
+    NextResult,
+    PreviousResult,
+    JumpToResult,
+    CloseResults,
+    // Link picker
+    NextLink,
+    PreviousLink,
+    OpenLink,
+    CopyLink,
+    CloseLinks,
+}
+
+/// Where keys are looked up: one of the input modes, or a popup or prompt that takes the
+/// keys while it's open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Normal,
+    Editing,
+    Search,
+    ReverseSearch,
+    SearchResults,
+    LinkPicker,
+}
+
+impl From<InputMode> for Scope {
+    fn from(mode: InputMode) -> Self {
+        match mode {
+            InputMode::Normal => Scope::Normal,
+            InputMode::Editing => Scope::Editing,
+        }
+    }
+}
+
+/// Actions with their default keys, in the order they're listed in the hints.
+const DEFAULT_BINDINGS: &[(Action, &[&str])] = &[
+    (Action::Quit, &["q"]),
+    (Action::StartEditing, &["enter"]),
+    (Action::SwitchTab, &["tab"]),
+    (Action::Search, &["/"]),
+    (Action::NextMatch, &["n"]),
+    (Action::PreviousMatch, &["N"]),
+    (Action::ToggleFilter, &["f"]),
+    (Action::Cancel, &["esc"]),
+    (Action::CycleLogLevel, &["l"]),
+    (Action::DumpLogs, &["d"]),
+    (Action::SelectNext, &["j", "down"]),
+    (Action::SelectPrevious, &["k", "up"]),
+    (Action::EditMessage, &["e"]),
+    (Action::Reply, &["r"]),
+    (Action::React, &["+"]),
+    (Action::ToggleThread, &["t"]),
+    (Action::ToggleRaw, &["m"]),
+    (Action::PickLink, &["u"]),
+    (Action::Send, &["enter"]),
+    (Action::Newline, &["shift+enter", "alt+enter"]),
+    (Action::StopEditing, &["esc"]),
+    (Action::HistoryOlder, &["up"]),
+    (Action::HistoryNewer, &["down"]),
+    (Action::SearchHistory, &["ctrl+r"]),
+    (Action::Complete, &["tab"]),
+    (Action::CompleteBack, &["shift+tab"]),
+    (Action::ConfirmSearch, &["enter"]),
+    (Action::CancelSearch, &["esc"]),
+    (Action::OlderMatch, &["ctrl+r"]),
+    (Action::AcceptMatch, &["enter"]),
+    (Action::CancelReverseSearch, &["esc", "ctrl+g"]),
+    (Action::NextResult, &["j", "down"]),
+    (Action::PreviousResult, &["k", "up"]),
+    (Action::JumpToResult, &["enter"]),
+    (Action::CloseResults, &["esc", "q"]),
+    (Action::NextLink, &["j", "down"]),
+    (Action::PreviousLink, &["k", "up"]),
+    (Action::OpenLink, &["enter", "o"]),
+    (Action::CopyLink, &["y"]),
+    (Action::CloseLinks, &["esc", "q"]),
+];
+
+impl Action {
+    pub fn scope(self) -> Scope {
+        match self {
+            Action::Send
+            | Action::Newline
+            | Action::StopEditing
+            | Action::HistoryOlder
+            | Action::HistoryNewer
+            | Action::SearchHistory
+            | Action::Complete
+            | Action::CompleteBack => Scope::Editing,
+            Action::ConfirmSearch | Action::CancelSearch => Scope::Search,
+            Action::AcceptMatch | Action::OlderMatch | Action::CancelReverseSearch => {
+                Scope::ReverseSearch
+            }
+            Action::NextResult
+            | Action::PreviousResult
+            | Action::JumpToResult
+            | Action::CloseResults => Scope::SearchResults,
+            Action::NextLink
+            | Action::PreviousLink
+            | Action::OpenLink
+            | Action::CopyLink
+            | Action::CloseLinks => Scope::LinkPicker,
+            _ => Scope::Normal,
+        }
+    }
+}
+
+/// A key with its modifiers, e.g. `ctrl+r`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Normalizes modifiers so that e.g. `N` matches whether or not the terminal reports Shift.
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code, modifiers }
+    }
+
+    /// Parses a key like `q`, `N`, `esc`, `shift+tab` or `ctrl+r`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let (modifier_names, key) = match text.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None if text == "+" => ("", "+"),
+            None => text.rsplit_once('+').unwrap_or(("", text)),
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for name in modifier_names.split('+').filter(|name| !name.is_empty()) {
+            modifiers |= match name.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => bail!("Unknown modifier {name} in key {text}"),
+            };
+        }
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => bail!("Unknown key {text}"),
+                },
+            },
+        };
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl From<KeyEvent> for KeyBinding {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::BackTab => write!(f, "shift+tab"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            KeyCode::PageUp => write!(f, "pgup"),
+            KeyCode::PageDown => write!(f, "pgdn"),
+            code => write!(f, "{}", format!("{code:?}").to_lowercase()),
+        }
+    }
+}
+
+/// The keys bound to each action, from the defaults and the user's keymap file.
+pub struct Keymap {
+    bindings: HashMap<Action, Vec<KeyBinding>>,
+    actions: HashMap<(Scope, KeyBinding), Action>,
+}
+
+/// The keymap file: for each mode or popup, actions and the keys replacing their defaults.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct KeymapFile {
+    normal: HashMap<Action, Vec<String>>,
+    editing: HashMap<Action, Vec<String>>,
+    search: HashMap<Action, Vec<String>>,
+    reverse_search: HashMap<Action, Vec<String>>,
+    search_results: HashMap<Action, Vec<String>>,
+    link_picker: HashMap<Action, Vec<String>>,
+}
+
+impl Keymap {
+    /// Loads the default keymap, with the user's keymap file applied on top if there is one.
+    pub fn load() -> Result<Self> {
+        let file = match Self::path().filter(|path| path.exists()) {
+            Some(path) => {
+                let contents = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                toml::from_str(&contents)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?
+            }
+            None => KeymapFile::default(),
+        };
+        Self::new(file).with_context(|| {
+            format!(
+                "Invalid keymap {}",
+                Self::path().unwrap_or_default().display()
+            )
+        })
+    }
+
+    fn new(file: KeymapFile) -> Result<Self> {
+        let mut bindings: HashMap<Action, Vec<KeyBinding>> = HashMap::new();
+        for (action, keys) in DEFAULT_BINDINGS {
+            let keys = keys
+                .iter()
+                .map(|key| KeyBinding::parse(key))
+                .collect::<Result<_>>()?;
+            bindings.insert(*action, keys);
+        }
+        for (scope, section, overrides) in [
+            (Scope::Normal, "normal", file.normal),
+            (Scope::Editing, "editing", file.editing),
+            (Scope::Search, "search", file.search),
+            (Scope::ReverseSearch, "reverse_search", file.reverse_search),
+            (Scope::SearchResults, "search_results", file.search_results),
+            (Scope::LinkPicker, "link_picker", file.link_picker),
+        ] {
+            for (action, keys) in overrides {
+                if action.scope() != scope {
+                    bail!("{action:?} can't be bound in [{section}]");
+                }
+                let keys = keys
+                    .iter()
+                    .map(|key| KeyBinding::parse(key))
+                    .collect::<Result<_>>()?;
+                bindings.insert(action, keys);
+            }
+        }
+
+        // Detect keys bound to more than one action in the same mode or popup
+        let mut actions = HashMap::new();
+        for (action, _) in DEFAULT_BINDINGS {
+            for key in &bindings[action] {
+                if let Some(other) = actions.insert((action.scope(), *key), *action) {
+                    bail!("Key {key} is bound to both {other:?} and {action:?}");
+                }
+            }
+        }
+        Ok(Self { bindings, actions })
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("keymap.toml"))
+    }
+
+    /// The action `key` is bound to in `scope`, an input mode or popup, if any.
+    pub fn action(&self, scope: impl Into<Scope>, key: KeyEvent) -> Option<Action> {
+        self.actions
+            .get(&(scope.into(), KeyBinding::from(key)))
+            .copied()
+    }
+
+    /// The first key bound to `action`, for hints. `none` if it was unbound.
+    pub fn key(&self, action: Action) -> String {
+        self.bindings[&action]
+            .first()
+            .map_or_else(|| "none".to_string(), KeyBinding::to_string)
+    }
+
+    /// Hints like `q: quit | j/k: select`, listing the first key of each group of actions.
+    pub fn hints(&self, hints: &[(&[Action], &str)]) -> String {
+        hints
+            .iter()
+            .map(|(actions, label)| {
+                let keys: Vec<String> = actions.iter().map(|action| self.key(*action)).collect();
+                format!("{}: {}", keys.join("/"), label)
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(toml: &str) -> Result<Keymap> {
+        Keymap::new(toml::from_str(toml)?)
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parses_keys_and_modifiers() {
+        let parse = |text| KeyBinding::parse(text).unwrap();
+        assert_eq!(
+            parse("q"),
+            KeyBinding::new(KeyCode::Char('q'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("esc"),
+            KeyBinding::new(KeyCode::Esc, KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("F5"),
+            KeyBinding::new(KeyCode::F(5), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("space"),
+            KeyBinding::new(KeyCode::Char(' '), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("ctrl+r"),
+            KeyBinding::new(KeyCode::Char('r'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            parse("Ctrl+Alt+enter"),
+            KeyBinding::new(KeyCode::Enter, KeyModifiers::CONTROL | KeyModifiers::ALT)
+        );
+        assert_eq!(
+            parse("shift+tab"),
+            KeyBinding::new(KeyCode::BackTab, KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("+"),
+            KeyBinding::new(KeyCode::Char('+'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("ctrl++"),
+            KeyBinding::new(KeyCode::Char('+'), KeyModifiers::CONTROL)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        assert!(KeyBinding::parse("hyper+q").is_err());
+        assert!(KeyBinding::parse("escape").is_err());
+        assert!(KeyBinding::parse("f13").is_err());
+        assert!(KeyBinding::parse("").is_err());
+    }
+
+    #[test]
+    fn shift_is_ignored_on_characters() {
+        assert_eq!(
+            KeyBinding::from(key(KeyCode::Char('N'), KeyModifiers::SHIFT)),
+            KeyBinding::parse("N").unwrap()
+        );
+        assert_eq!(
+            KeyBinding::from(key(KeyCode::BackTab, KeyModifiers::SHIFT)),
+            KeyBinding::parse("shift+tab").unwrap()
+        );
+    }
+
+    #[test]
+    fn displays_keys_as_written() {
+        for text in [
+            "q",
+            "ctrl+r",
+            "shift+enter",
+            "shift+tab",
+            "space",
+            "f5",
+            "esc",
+        ] {
+            assert_eq!(KeyBinding::parse(text).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn default_keymap_has_no_conflicts() {
+        let keymap = keymap("").unwrap();
+        assert_eq!(
+            keymap.action(Scope::Normal, key(KeyCode::Char('q'), KeyModifiers::NONE)),
+            Some(Action::Quit)
+        );
+        // The same key means different things in different scopes
+        let enter = key(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(keymap.action(Scope::Editing, enter), Some(Action::Send));
+        assert_eq!(
+            keymap.action(Scope::LinkPicker, enter),
+            Some(Action::OpenLink)
+        );
+    }
+
+    #[test]
+    fn overrides_replace_the_default_keys() {
+        let keymap = keymap("[normal]\nquit = [\"ctrl+q\"]\nreply = []\n").unwrap();
+        let q = key(KeyCode::Char('q'), KeyModifiers::NONE);
+        assert_eq!(keymap.action(Scope::Normal, q), None);
+        assert_eq!(
+            keymap.action(
+                Scope::Normal,
+                key(KeyCode::Char('q'), KeyModifiers::CONTROL)
+            ),
+            Some(Action::Quit)
+        );
+        assert_eq!(keymap.key(Action::Quit), "ctrl+q");
+        assert_eq!(keymap.key(Action::Reply), "none");
+        assert_eq!(
+            keymap.hints(&[(&[Action::SelectNext, Action::SelectPrevious], "select")]),
+            "j/k: select"
+        );
+    }
+
+    #[test]
+    fn reports_keys_bound_twice_in_a_scope() {
+        let error = keymap("[normal]\nreply = [\"q\"]\n").err().unwrap();
+        assert!(error.to_string().contains("bound to both"), "{error}");
+        // Fine in different scopes
+        assert!(keymap("[editing]\nsend = [\"q\"]\n").is_ok());
+    }
+
+    #[test]
+    fn actions_only_go_in_their_own_section() {
+        let error = keymap("[normal]\nsend = [\"x\"]\n").err().unwrap();
+        assert!(error.to_string().contains("[normal]"), "{error}");
+        assert!(keymap("[visual]\nquit = [\"x\"]\n").is_err());
+        assert!(keymap("[normal]\nquit = [\"nope\"]\n").is_err());
+    }
+}
@@ -17,33 +17,56 @@ pub use network_manager::*;
 pub mod logging;
 pub use logging::*;
 
-pub async fn run_app(mut model: Model<'_>, mut tui: Tui) -> Result<()> {
+pub mod config;
+pub use config::*;
+
+pub mod command;
+pub use command::*;
+
+pub mod keymap;
+pub use keymap::*;
+
+pub mod theme;
+pub use theme::*;
+
+pub mod notification;
+pub use notification::*;
+
+pub mod emoji;
+
+pub mod markup;
+
+pub mod links;
+
+pub async fn run_app(mut model: Model, mut tui: Tui) -> Result<()> {
     tui.enter()?;
     let mut should_exit = false;
     loop {
         tokio::select! {
-                Some(message) = tui.next() => {
-                    match message {
-                        Message::Render => {
-                            // Update FPS counter
-                            model.fps_counter.tick();
-                            // Handle the render event
-                            tui.terminal.draw(|f| {
-                                view(f, &model);
-                            })?;
-                        },
-                        Message::Quit => {
-                            should_exit = true;
-                        },
-                        message => {
-                            update(&mut model, message);
+            Some(message) = tui.next() => {
+                match message {
+                    Message::Render => {
+                        if model.dirty || model.config.render_mode == RenderMode::FixedRate {
+                            draw(&mut tui, &mut model)?;
                         }
+                    },
+                    Message::Resize(..) => {
+                        // Redraw right away rather than showing a garbled screen until the next frame
+                        tui.terminal.autoresize()?;
+                        draw(&mut tui, &mut model)?;
+                    },
+                    Message::Quit => {
+                        should_exit = true;
+                    },
+                    message => {
+                        update(&mut model, message);
                     }
-                },
-                Some(network_msg) = model.network_manager.get_incoming_messages().recv() => {
-                    update(&mut model, Message::ReceivedNetworkMessage(network_msg));
-                },
-            }
+                }
+            },
+            Some(network_msg) = model.network_manager.get_incoming_messages().recv() => {
+                update(&mut model, Message::ReceivedNetworkMessage(network_msg));
+            },
+        }
         if should_exit {
             break;
         }
@@ -51,3 +74,18 @@ pub async fn run_app(mut model: Model<'_>, mut tui: Tui) -> Result<()> {
     tui.exit()?;
     Ok(())
 }
+
+fn draw(tui: &mut Tui, model: &mut Model) -> Result<()> {
+    model.dirty = false;
+    // Update FPS counter
+    model.fps_counter.tick();
+    let mut click_areas = ClickAreas::default();
+    let frame = tui.terminal.draw(|f| {
+        click_areas = view(f, model);
+    })?;
+    if model.config.hyperlinks {
+        links::write_hyperlinks(frame.buffer, &click_areas.links)?;
+    }
+    model.click_areas = click_areas;
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use std::io::Write;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+use crossterm::cursor::{MoveTo, RestorePosition, SavePosition};
+use crossterm::queue;
+use crossterm::style::{Attribute, Print, ResetColor, SetAttribute, SetColors};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Modifier;
+use unicode_width::UnicodeWidthStr;
+
+/// Byte ranges of the `http://` and `https://` links in `text`. Punctuation at the end of a
+/// link, like the period ending a sentence or a closing parenthesis without an opening one
+/// in the link, isn't part of it.
+pub fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut search_from = 0;
+    while let Some(offset) = text[search_from..].find("http") {
+        let start = search_from + offset;
+        let rest = &text[start..];
+        let starts_word = !text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric);
+        let scheme_len = ["https://", "http://"]
+            .iter()
+            .find(|scheme| rest.starts_with(*scheme))
+            .map(|scheme| scheme.len());
+        let (Some(scheme_len), true) = (scheme_len, starts_word) else {
+            search_from = start + "http".len();
+            continue;
+        };
+        let len = rest
+            .find(|c: char| {
+                c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | '"' | '`')
+            })
+            .unwrap_or(rest.len());
+        let mut url = &rest[..len];
+        loop {
+            let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '*', '_']);
+            let trimmed = match trimmed.strip_suffix(')') {
+                Some(inner) if inner.matches('(').count() < trimmed.matches(')').count() => inner,
+                _ => trimmed,
+            };
+            if trimmed.len() == url.len() {
+                break;
+            }
+            url = trimmed;
+        }
+        if url.len() > scheme_len {
+            urls.push(start..start + url.len());
+        }
+        search_from = start + len.max(1);
+    }
+    urls
+}
+
+/// Where each of `urls` was drawn in `area` of `buffer`, one single row area for each row
+/// a link takes up. Links are found in the order given, in rows read left to right and
+/// top to bottom, so a link that was wrapped is found across the rows it was wrapped over.
+pub fn find_link_areas(buffer: &Buffer, area: Rect, urls: &[String]) -> Vec<(Rect, String)> {
+    // The text of the rows without the indent and padding around it, and the cell each
+    // byte of it was drawn in
+    let mut text = String::new();
+    let mut cells = Vec::new();
+    for y in area.top()..area.bottom() {
+        let mut row = Vec::new();
+        let mut x = area.left();
+        while x < area.right() {
+            let symbol = buffer[(x, y)].symbol();
+            row.push((x, symbol));
+            x += symbol.width().max(1) as u16;
+        }
+        let start = row.iter().position(|(_, symbol)| !symbol.trim().is_empty());
+        let end = row
+            .iter()
+            .rposition(|(_, symbol)| !symbol.trim().is_empty());
+        let (Some(start), Some(end)) = (start, end) else {
+            continue;
+        };
+        for (x, symbol) in &row[start..=end] {
+            text.push_str(symbol);
+            cells.extend(std::iter::repeat_n(
+                (*x, y, symbol.width() as u16),
+                symbol.len(),
+            ));
+        }
+    }
+
+    let mut areas: Vec<(Rect, String)> = Vec::new();
+    let mut search_from = 0;
+    for url in urls {
+        let Some(offset) = text[search_from..].find(url.as_str()) else {
+            continue;
+        };
+        let start = search_from + offset;
+        search_from = start + url.len();
+        for (x, y, width) in &cells[start..search_from] {
+            match areas.last_mut() {
+                Some((area, last_url)) if last_url == url && area.y == *y => {
+                    *area = area.union(Rect::new(*x, *y, *width, 1));
+                }
+                _ => areas.push((Rect::new(*x, *y, *width, 1), url.clone())),
+            }
+        }
+    }
+    areas
+}
+
+/// Draws `links` over the text already on the screen again, this time wrapped in OSC 8
+/// hyperlinks so the terminal lets them be clicked. ratatui miscounts the width of cells
+/// holding escape sequences, so this is done after it's done drawing.
+pub fn write_hyperlinks(buffer: &Buffer, links: &[(Rect, String)]) -> Result<()> {
+    if links.is_empty() {
+        return Ok(());
+    }
+    let mut stdout = std::io::stdout();
+    queue!(stdout, SavePosition)?;
+    for (area, url) in links {
+        queue!(
+            stdout,
+            MoveTo(area.x, area.y),
+            Print(format!("\x1b]8;;{}\x1b\\", escape_url(url)))
+        )?;
+        let mut x = area.left();
+        while x < area.right() {
+            let cell = &buffer[(x, area.y)];
+            queue!(
+                stdout,
+                SetAttribute(Attribute::Reset),
+                SetColors(crossterm::style::Colors::new(
+                    cell.fg.into(),
+                    cell.bg.into()
+                ))
+            )?;
+            for (modifier, attribute) in [
+                (Modifier::BOLD, Attribute::Bold),
+                (Modifier::DIM, Attribute::Dim),
+                (Modifier::ITALIC, Attribute::Italic),
+                (Modifier::UNDERLINED, Attribute::Underlined),
+                (Modifier::REVERSED, Attribute::Reverse),
+            ] {
+                if cell.modifier.contains(modifier) {
+                    queue!(stdout, SetAttribute(attribute))?;
+                }
+            }
+            queue!(stdout, Print(cell.symbol()))?;
+            x += cell.symbol().width().max(1) as u16;
+        }
+        queue!(stdout, Print("\x1b]8;;\x1b\\"))?;
+    }
+    queue!(
+        stdout,
+        SetAttribute(Attribute::Reset),
+        ResetColor,
+        RestorePosition
+    )?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// `url` with everything but printable ASCII percent-encoded, so it can't end the OSC 8
+/// sequence it's written in early and slip in escape sequences of its own.
+fn escape_url(url: &str) -> String {
+    let mut escaped = String::with_capacity(url.len());
+    for byte in url.bytes() {
+        if byte.is_ascii_graphic() {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    escaped
+}
+
+/// Puts `text` on the clipboard through the terminal, with OSC 52. This works over SSH too,
+/// though some terminals need it to be allowed first.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Opens `url` with `command`, a program and its arguments separated by spaces, without
+/// waiting for it to finish.
+pub fn open_url(command: &str, url: &str) -> Result<()> {
+    let mut words = command.split_whitespace();
+    let Some(program) = words.next() else {
+        bail!("No open_command configured");
+    };
+    Command::new(program)
+        .args(words)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {program}"))?;
+    Ok(())
+}
+
+/// The standard base64 encoding of `bytes`, with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (idx, byte)| {
+            n | u32::from(*byte) << (16 - 8 * idx)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * idx) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(text: &str) -> Vec<&str> {
+        find_urls(text).into_iter().map(|url| &text[url]).collect()
+    }
+
+    #[test]
+    fn finds_links_between_text() {
+        assert_eq!(
+            urls("see https://example.com and http://a.org/x?y=1#z too"),
+            ["https://example.com", "http://a.org/x?y=1#z"]
+        );
+    }
+
+    #[test]
+    fn trailing_punctuation_isnt_part_of_a_link() {
+        assert_eq!(urls("Go to https://example.com."), ["https://example.com"]);
+        assert_eq!(urls("https://example.com/a, or"), ["https://example.com/a"]);
+        assert_eq!(urls("*https://example.com*"), ["https://example.com"]);
+    }
+
+    #[test]
+    fn parentheses_are_kept_when_balanced() {
+        assert_eq!(
+            urls("https://en.wikipedia.org/wiki/Foo_(bar)"),
+            ["https://en.wikipedia.org/wiki/Foo_(bar)"]
+        );
+        assert_eq!(urls("(see https://example.com)"), ["https://example.com"]);
+    }
+
+    #[test]
+    fn control_characters_end_a_link() {
+        assert_eq!(
+            urls("https://a.b/x\x1b\\\x1b]52;c;aGk=\x07"),
+            ["https://a.b/x"]
+        );
+        assert_eq!(urls("https://a.b\x07y"), ["https://a.b"]);
+    }
+
+    #[test]
+    fn escapes_urls_written_into_osc_8() {
+        assert_eq!(escape_url("https://a.b/x?y=1#z"), "https://a.b/x?y=1#z");
+        assert_eq!(escape_url("https://a.b/\x1b\\"), "https://a.b/%1B\\");
+        assert_eq!(escape_url("https://a.b/é"), "https://a.b/%C3%A9");
+    }
+
+    #[test]
+    fn ignores_schemes_inside_words_and_bare_schemes() {
+        assert!(urls("xhttps://example.com").is_empty());
+        assert!(urls("https:// nothing").is_empty());
+        assert!(urls("http is a protocol").is_empty());
+    }
+
+    fn buffer(rows: &[&str]) -> (Buffer, Rect) {
+        let area = Rect::new(0, 0, 20, rows.len() as u16);
+        let mut buffer = Buffer::empty(area);
+        for (y, row) in rows.iter().enumerate() {
+            buffer.set_string(0, y as u16, row, ratatui::style::Style::default());
+        }
+        (buffer, area)
+    }
+
+    #[test]
+    fn finds_link_areas_on_one_row() {
+        let (buffer, area) = buffer(&["ann: see https://a.b"]);
+        assert_eq!(
+            find_link_areas(&buffer, area, &["https://a.b".to_string()]),
+            [(Rect::new(9, 0, 11, 1), "https://a.b".to_string())]
+        );
+    }
+
+    #[test]
+    fn finds_link_areas_across_wrapped_rows() {
+        let (buffer, area) = buffer(&["ann: https://exampl", "     e.com/path end"]);
+        let url = "https://example.com/path".to_string();
+        assert_eq!(
+            find_link_areas(&buffer, area, std::slice::from_ref(&url)),
+            [
+                (Rect::new(5, 0, 14, 1), url.clone()),
+                (Rect::new(5, 1, 10, 1), url)
+            ]
+        );
+    }
+
+    #[test]
+    fn links_not_drawn_are_skipped() {
+        let (buffer, area) = buffer(&["nothing here"]);
+        assert!(find_link_areas(&buffer, area, &["https://a.b".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn encodes_base64_with_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0xfe]), "//4=");
+    }
+}
@@ -1,45 +1,128 @@
+use anyhow::{anyhow, Result};
 use chrono::Local;
 use core::fmt;
-use ratatui::{
-    style::{Color, Style},
-    text::Line,
-    widgets::ListItem,
-};
 use std::fmt::Write;
+use std::path::PathBuf;
 use tracing::{
     field::{Field, Visit},
     Event, Level, Subscriber,
 };
-use tracing_subscriber::Layer;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+use crate::{state_dir, Message};
+
+/// A log event captured for the Logs tab.
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: Level,
+    pub target: String,
+    /// Names of the spans the event happened in, outermost first.
+    pub spans: Vec<String>,
+    pub message: String,
+    /// Every field of the event other than `message`.
+    pub fields: Vec<(String, String)>,
+}
+
+impl LogEntry {
+    /// The span names and target, e.g. `connect:client::network_manager`.
+    pub fn context(&self) -> String {
+        let mut context = String::new();
+        for span in &self.spans {
+            write!(context, "{span}:").unwrap();
+        }
+        context.push_str(&self.target);
+        context
+    }
+
+    /// The structured fields as `key=value` pairs.
+    pub fn fields_text(&self) -> String {
+        self.fields
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The entry as a single line of text, as shown in the Logs tab.
+    pub fn text(&self) -> String {
+        let mut text = format!(
+            "{} {:<5} {}: {}",
+            self.timestamp,
+            self.level.as_str(),
+            self.context(),
+            self.message
+        );
+        if !self.fields.is_empty() {
+            write!(text, " {}", self.fields_text()).unwrap();
+        }
+        text
+    }
+}
 
-use crate::Message;
+/// The next, less verbose, minimum level to show in the Logs tab, wrapping back to `TRACE`.
+pub fn next_log_level(level: Level) -> Level {
+    match level {
+        Level::TRACE => Level::DEBUG,
+        Level::DEBUG => Level::INFO,
+        Level::INFO => Level::WARN,
+        Level::WARN => Level::ERROR,
+        Level::ERROR => Level::TRACE,
+    }
+}
+
+/// Writes `logs` to a new, timestamped file in the state directory and returns its path.
+pub fn dump_logs<'a>(logs: impl Iterator<Item = &'a LogEntry>) -> Result<PathBuf> {
+    let dir = state_dir().ok_or_else(|| anyhow!("No state directory to dump the logs to"))?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "log-dump-{}.log",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let mut contents = String::new();
+    for log in logs {
+        writeln!(contents, "{}", log.text())?;
+    }
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
 
 pub struct TuiLogLayer {
     pub message_tx: tokio::sync::mpsc::UnboundedSender<Message>,
 }
 
-impl<S: Subscriber> Layer<S> for TuiLogLayer {
-    fn on_event(&self, event: &Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
-        let timestamp = Local::now().format("%H:%M:%S");
-
-        let level = event.metadata().level();
-        let (level_str, color) = match *level {
-            Level::ERROR => ("ERROR", Color::Red),
-            Level::INFO => ("INFO", Color::Green),
-            Level::WARN => ("WARN", Color::Yellow),
-            _ => ("OTHER", Color::White),
-        };
+impl<S> Layer<S> for TuiLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let timestamp = Local::now().format("%H:%M:%S").to_string();
 
         let mut message = String::new();
-        let mut vistor = MessageVisitor::new(&mut message);
+        let mut fields = Vec::new();
+        let mut vistor = MessageVisitor::new(&mut message, &mut fields);
         event.record(&mut vistor);
 
-        let log_line = Line::styled(
-            format!("{timestamp} [{level_str}] {message}"),
-            Style::default().fg(color),
-        );
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| span.name().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let log_entry = LogEntry {
+            timestamp,
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_string(),
+            spans,
+            message,
+            fields,
+        };
 
-        if let Err(e) = self.message_tx.send(Message::Log(ListItem::new(log_line))) {
+        if let Err(e) = self.message_tx.send(Message::Log(log_entry)) {
             eprintln!("Failed to send log message: {}", e);
         }
     }
@@ -47,23 +130,34 @@ impl<S: Subscriber> Layer<S> for TuiLogLayer {
 
 pub struct MessageVisitor<'a> {
     message: &'a mut String,
-    is_message_field: bool,
+    fields: &'a mut Vec<(String, String)>,
 }
 
 impl<'a> MessageVisitor<'a> {
-    pub fn new(string: &'a mut String) -> Self {
+    pub fn new(string: &'a mut String, fields: &'a mut Vec<(String, String)>) -> Self {
         MessageVisitor {
             message: string,
-            is_message_field: false,
+            fields,
         }
     }
 }
 
 impl<'a> Visit for MessageVisitor<'a> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.fields
+                .push((field.name().to_string(), value.to_string()));
+        }
+    }
+
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
         if field.name() == "message" {
             write!(self.message, "{:?}", value).unwrap();
-            self.is_message_field = true;
+        } else {
+            self.fields
+                .push((field.name().to_string(), format!("{:?}", value)));
         }
     }
 }
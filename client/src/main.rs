@@ -1,35 +1,73 @@
-use client::{run_app, Model, NetworkManager, Tui, TuiLogLayer};
 use anyhow::{Context, Result};
-use tracing_subscriber::{layer::SubscriberExt, Registry};
+use client::{
+    run_app, state_dir, Config, InputHistory, Keymap, Model, NetworkManager, Theme, Tui,
+    TuiLogLayer,
+};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::{layer::SubscriberExt, Layer, Registry};
+
+const SERVER_ADDR: &str = "localhost:8080";
 
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
-        eprintln!("{}", e);
+        eprintln!("{:#}", e);
         std::process::exit(1);
     }
 }
 
 pub async fn run() -> Result<()> {
-    let tui = Tui::new(4.0, 30.0).context("Failed to initialize the terminal user interface (TUI)")?;
+    let config = Config::load()?;
+    let keymap = Keymap::load()?;
+    let theme = Theme::load(&config.theme)?;
+
+    let tui =
+        Tui::new(4.0, 30.0).context("Failed to initialize the terminal user interface (TUI)")?;
 
     let log_layer = TuiLogLayer {
         message_tx: tui.event_tx.clone(),
     };
 
-    let subscriber = Registry::default().with(log_layer);
+    // Daily rotated log files in the state directory, keeping the last week. Only INFO and
+    // above go there, so message contents logged at DEBUG stay off the disk.
+    let appender = state_dir().filter(|_| config.log_to_file).map(|dir| {
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("client")
+            .filename_suffix("log")
+            .max_log_files(7)
+            .build(dir)
+    });
+    let (file_layer, _file_guard, file_error) = match appender {
+        Some(Ok(appender)) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_writer(writer)
+                .with_ansi(false)
+                .with_filter(LevelFilter::INFO);
+            (Some(layer), Some(guard), None)
+        }
+        Some(Err(e)) => (None, None, Some(e)),
+        None => (None, None, None),
+    };
+
+    let subscriber = Registry::default().with(log_layer).with(file_layer);
     tracing::subscriber::set_global_default(subscriber)?;
+    if let Some(e) = file_error {
+        tracing::warn!("Failed to create the log file, logs won't be saved: {}", e);
+    }
 
-    let network_manager = NetworkManager::connect_to_server("localhost:8080")
+    let network_manager = NetworkManager::connect_to_server(SERVER_ADDR)
         .await
-        .context("Failed to connect to the network server at localhost:8080")?;
-
-    let model = Model::new(&tui, network_manager);
+        .with_context(|| format!("Failed to connect to the network server at {SERVER_ADDR}"))?;
 
-    run_app(
-        model, tui,
-    ).await.context("Failed to start the application")?;
+    let input_history = InputHistory::load(SERVER_ADDR);
+    let model = Model::new(&tui, network_manager, config, input_history, keymap, theme);
 
+    run_app(model, tui)
+        .await
+        .context("Failed to start the application")?;
 
     Ok(())
 }
@@ -0,0 +1,267 @@
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Span;
+
+use crate::links::find_urls;
+use crate::Theme;
+
+/// Turns the lightweight markup in a message body into styled spans: `*bold*`, `_italic_`,
+/// `` `code` `` or ```` ```code``` ````, blocks fenced by ```` ``` ```` lines and `> ` quotes.
+/// Line breaks are kept in the span contents.
+pub fn markup(body: &str, style: Style, theme: &Theme) -> Vec<Span<'static>> {
+    let lines: Vec<&str> = body.split('\n').collect();
+    let mut spans = Vec::new();
+    let mut in_code_block = false;
+    let mut first_line = true;
+    for (idx, line) in lines.iter().enumerate() {
+        // The fences themselves aren't shown. A fence without a closing one is just text.
+        if is_fence(line) && (in_code_block || lines[idx + 1..].iter().any(|line| is_fence(line))) {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if !first_line {
+            spans.push(Span::raw("\n"));
+        }
+        first_line = false;
+        if in_code_block {
+            spans.push(Span::styled(line.to_string(), style.patch(theme.code)));
+        } else if let Some(quote) = line.strip_prefix('>') {
+            let quote_style = style.patch(theme.quote);
+            spans.push(Span::styled("│ ", quote_style));
+            inline(
+                quote.strip_prefix(' ').unwrap_or(quote),
+                quote_style,
+                theme,
+                &mut spans,
+            );
+        } else {
+            inline(line, style, theme, &mut spans);
+        }
+    }
+    spans
+}
+
+/// Whether `line` opens or closes a code block: three backticks and an optional language,
+/// like ```` ```rust ````.
+fn is_fence(line: &str) -> bool {
+    line.trim().strip_prefix("```").is_some_and(|language| {
+        language
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '+' | '-'))
+    })
+}
+
+/// `body` in `style` as typed, with only its links styled.
+pub fn plain(body: &str, style: Style, theme: &Theme) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut last_end = 0;
+    for url in find_urls(body) {
+        if url.start > last_end {
+            spans.push(Span::styled(body[last_end..url.start].to_string(), style));
+        }
+        spans.push(Span::styled(
+            body[url.clone()].to_string(),
+            style.patch(theme.link),
+        ));
+        last_end = url.end;
+    }
+    if last_end < body.len() {
+        spans.push(Span::styled(body[last_end..].to_string(), style));
+    }
+    spans
+}
+
+/// Styles the code, bold and italic parts and the links of a single line. Bold and italic
+/// can be nested, links are never formatted.
+fn inline(text: &str, style: Style, theme: &Theme, spans: &mut Vec<Span<'static>>) {
+    let chars: Vec<char> = text.chars().collect();
+    // The links, as character ranges
+    let urls: Vec<(usize, usize)> = find_urls(text)
+        .into_iter()
+        .map(|url| {
+            let start = text[..url.start].chars().count();
+            (start, start + text[url].chars().count())
+        })
+        .collect();
+    let mut plain = String::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+        if let Some((_, end)) = urls.iter().find(|(start, _)| *start == idx) {
+            if !plain.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut plain), style));
+            }
+            let url: String = chars[idx..*end].iter().collect();
+            spans.push(Span::styled(url, style.patch(theme.link)));
+            idx = *end;
+            continue;
+        }
+        let c = chars[idx];
+        if chars[idx..].starts_with(&['`'; 3]) {
+            let end = (idx + 4..chars.len().saturating_sub(2))
+                .find(|end| chars[*end..].starts_with(&['`'; 3]));
+            if let Some(end) = end {
+                if !plain.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut plain), style));
+                }
+                let code: String = chars[idx + 3..end].iter().collect();
+                spans.push(Span::styled(code, style.patch(theme.code)));
+                idx = end + 3;
+                continue;
+            }
+        }
+        let styled = match c {
+            '`' => chars[idx + 1..]
+                .iter()
+                .position(|other| *other == '`')
+                .filter(|len| *len > 0)
+                .map(|len| (idx + 1 + len, None)),
+            '*' if opens(&chars, idx) => closes(&chars, idx).map(|end| (end, Some(Modifier::BOLD))),
+            '_' if opens(&chars, idx) => {
+                closes(&chars, idx).map(|end| (end, Some(Modifier::ITALIC)))
+            }
+            _ => None,
+        };
+        let Some((end, modifier)) = styled else {
+            plain.push(c);
+            idx += 1;
+            continue;
+        };
+        if !plain.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut plain), style));
+        }
+        let inner: String = chars[idx + 1..end].iter().collect();
+        match modifier {
+            Some(modifier) => inline(&inner, style.add_modifier(modifier), theme, spans),
+            None => spans.push(Span::styled(inner, style.patch(theme.code))),
+        }
+        idx = end + 1;
+    }
+    if !plain.is_empty() {
+        spans.push(Span::styled(plain, style));
+    }
+}
+
+/// Whether the `*` or `_` at `idx` can start emphasis: at the start of a word and followed
+/// by text, so `snake_case`, `2 * 3` and doubled markers like `**` are left alone.
+fn opens(chars: &[char], idx: usize) -> bool {
+    let marker = chars[idx];
+    let before = idx.checked_sub(1).map(|before| chars[before]);
+    let after = chars.get(idx + 1);
+    !before.is_some_and(|before| before.is_alphanumeric() || before == marker)
+        && after.is_some_and(|after| !after.is_whitespace() && *after != marker)
+}
+
+/// The position of the marker that ends the emphasis opened at `idx`, if there is one.
+fn closes(chars: &[char], idx: usize) -> Option<usize> {
+    let marker = chars[idx];
+    (idx + 2..chars.len()).find(|end| {
+        chars[*end] == marker
+            && !chars[end - 1].is_whitespace()
+            && chars[end - 1] != marker
+            && !chars
+                .get(end + 1)
+                .is_some_and(|after| after.is_alphanumeric() || *after == marker)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The spans of `body` as text and whether each is code, bold or italic.
+    fn render(body: &str) -> Vec<(String, &'static str)> {
+        let theme = Theme::dark();
+        markup(body, Style::default(), &theme)
+            .into_iter()
+            .map(|span| {
+                let kind = if span.style == theme.code {
+                    "code"
+                } else if span.style.add_modifier.contains(Modifier::BOLD) {
+                    "bold"
+                } else if span.style.add_modifier.contains(Modifier::ITALIC) {
+                    "italic"
+                } else if span.style == theme.link {
+                    "link"
+                } else {
+                    ""
+                };
+                (span.content.to_string(), kind)
+            })
+            .collect()
+    }
+
+    fn owned(spans: &[(&str, &'static str)]) -> Vec<(String, &'static str)> {
+        spans
+            .iter()
+            .map(|(text, kind)| (text.to_string(), *kind))
+            .collect()
+    }
+
+    #[test]
+    fn formats_inline_markup() {
+        assert_eq!(
+            render("a *b* _c_ `d`"),
+            owned(&[
+                ("a ", ""),
+                ("b", "bold"),
+                (" ", ""),
+                ("c", "italic"),
+                (" ", ""),
+                ("d", "code")
+            ])
+        );
+    }
+
+    #[test]
+    fn inline_triple_backticks_are_code() {
+        assert_eq!(render("```let x = 1;```"), owned(&[("let x = 1;", "code")]));
+        assert_eq!(
+            render("run ```ls -l``` now"),
+            owned(&[("run ", ""), ("ls -l", "code"), (" now", "")])
+        );
+    }
+
+    #[test]
+    fn fenced_code_block_hides_fences() {
+        assert_eq!(
+            render("```rust\nlet x = 1;\n```\nafter"),
+            owned(&[("let x = 1;", "code"), ("\n", ""), ("after", "")])
+        );
+    }
+
+    #[test]
+    fn unclosed_fence_is_text() {
+        assert_eq!(
+            render("```\ncode"),
+            owned(&[("```", ""), ("\n", ""), ("code", "")])
+        );
+    }
+
+    #[test]
+    fn doubled_markers_are_left_alone() {
+        assert_eq!(render("**bold**"), owned(&[("**bold**", "")]));
+        assert_eq!(render("__init__"), owned(&[("__init__", "")]));
+    }
+
+    #[test]
+    fn markers_inside_words_are_left_alone() {
+        assert_eq!(render("snake_case_name"), owned(&[("snake_case_name", "")]));
+        assert_eq!(render("2 * 3 * 4"), owned(&[("2 * 3 * 4", "")]));
+    }
+
+    #[test]
+    fn links_are_not_formatted() {
+        assert_eq!(
+            render("see https://example.com/a_b_c"),
+            owned(&[("see ", ""), ("https://example.com/a_b_c", "link")])
+        );
+    }
+
+    #[test]
+    fn quotes_get_a_bar() {
+        let theme = Theme::dark();
+        let spans = markup("> hi", Style::default(), &theme);
+        assert_eq!(spans[0].content, "│ ");
+        assert_eq!(spans[1].content, "hi");
+        assert_eq!(spans[1].style, theme.quote);
+    }
+}
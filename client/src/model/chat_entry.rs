@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use protocol::{ChatMessage, Notice};
+
+/// A single line in the chat view.
+pub enum ChatEntry {
+    Message(ChatMessage),
+    Notice(Notice),
+}
+
+impl ChatEntry {
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            ChatEntry::Message(message) => message.timestamp,
+            ChatEntry::Notice(notice) => notice.timestamp,
+        }
+    }
+
+    /// The entry as plain text, without its timestamp.
+    pub fn text(&self) -> String {
+        match self {
+            ChatEntry::Message(message) => {
+                let author = match &message.to {
+                    Some(to) => format!("{} → {}", message.author, to),
+                    None => message.author.clone(),
+                };
+                let body = if message.deleted {
+                    "message deleted"
+                } else {
+                    &message.body
+                };
+                format!("{author}: {body}")
+            }
+            ChatEntry::Notice(notice) => notice.text.clone(),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use ratatui::layout::{Position, Rect};
+
+use crate::model::model::ActiveTab;
+
+/// Where the clickable parts of the UI were drawn in the last frame, for mouse support.
+#[derive(Clone, Debug, Default)]
+pub struct ClickAreas {
+    pub tabs: Vec<(Rect, ActiveTab)>,
+    /// Inside of the input box, and how many lines and columns its text is scrolled by.
+    pub input: Option<(Rect, (usize, usize))>,
+    /// Author names of the chat messages in view.
+    pub usernames: Vec<(Rect, String)>,
+    /// Links in the chat view, one area for each row they take up, made into hyperlinks
+    /// after drawing.
+    pub links: Vec<(Rect, String)>,
+    /// The first row of the chat in view and the last row it can be scrolled to, to scroll
+    /// the chat by row.
+    pub chat_rows: (usize, usize),
+}
+
+impl ClickAreas {
+    pub fn tab_at(&self, position: Position) -> Option<ActiveTab> {
+        self.tabs
+            .iter()
+            .find(|(area, _)| area.contains(position))
+            .map(|(_, tab)| *tab)
+    }
+
+    /// The line and column of the input that was clicked, if `position` is in the input box.
+    pub fn input_cursor_at(&self, position: Position) -> Option<(usize, usize)> {
+        let (area, (scroll_y, scroll_x)) = self.input?;
+        area.contains(position).then(|| {
+            (
+                scroll_y + (position.y - area.y) as usize,
+                scroll_x + (position.x - area.x) as usize,
+            )
+        })
+    }
+
+    pub fn username_at(&self, position: Position) -> Option<&str> {
+        self.usernames
+            .iter()
+            .find(|(area, _)| area.contains(position))
+            .map(|(_, username)| username.as_str())
+    }
+}
@@ -0,0 +1,190 @@
+use protocol::LOBBY;
+use tui_input::Input;
+
+use crate::{emoji::SHORTCODES, COMMAND_NAMES};
+
+/// Tab completion of the word before the cursor, cycling through the candidates.
+pub struct Completion {
+    /// Input up to the word being completed.
+    before: String,
+    /// Input after the cursor.
+    after: String,
+    /// The word as typed, put back when the completion is cancelled.
+    word: String,
+    pub candidates: Vec<String>,
+    pub selected: usize,
+}
+
+impl Completion {
+    /// Starts completing the word before the cursor: a command name, a command argument or
+    /// a nickname out of `users`. `None` if nothing matches.
+    pub fn start(input: &Input, users: &[String]) -> Option<Self> {
+        let before_cursor: String = input.value().chars().take(input.cursor()).collect();
+        let after: String = input.value().chars().skip(input.cursor()).collect();
+        let word_start = before_cursor.rfind(char::is_whitespace).map_or(0, |idx| {
+            idx + before_cursor[idx..].chars().next().unwrap().len_utf8()
+        });
+        let (before, word) = before_cursor.split_at(word_start);
+
+        let candidates = candidates(before, word, users);
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(Self {
+            before: before.to_string(),
+            after,
+            word: word.to_string(),
+            candidates,
+            selected: 0,
+        })
+    }
+
+    pub fn cycle(&mut self, offset: isize) {
+        let len = self.candidates.len() as isize;
+        self.selected = (self.selected as isize + offset).rem_euclid(len) as usize;
+    }
+
+    /// The input with the selected candidate in place of the word, followed by a space
+    /// unless the candidate is a `key:` to type a value after.
+    pub fn apply(&self) -> Input {
+        let candidate = &self.candidates[self.selected];
+        let mut value = format!("{}{}", self.before, candidate);
+        if !candidate.ends_with(':') && !self.after.starts_with(char::is_whitespace) {
+            value.push(' ');
+        }
+        let cursor = value.chars().count();
+        value.push_str(&self.after);
+        Input::new(value).with_cursor(cursor)
+    }
+
+    /// The input as it was before completing.
+    pub fn original(&self) -> Input {
+        let value = format!("{}{}", self.before, self.word);
+        let cursor = value.chars().count();
+        Input::new(format!("{value}{}", self.after)).with_cursor(cursor)
+    }
+
+    /// Character offset in the input where the completed word starts, to place the popup.
+    pub fn word_start(&self) -> usize {
+        self.before.chars().count()
+    }
+}
+
+/// Completions for `word`, given the input `before` it.
+fn candidates(before: &str, word: &str, users: &[String]) -> Vec<String> {
+    let command = before
+        .strip_prefix('/')
+        .and_then(|rest| rest.split_whitespace().next());
+    if word.is_empty() && command.is_none() {
+        return Vec::new();
+    }
+    let argument = before.split_whitespace().count();
+    let mut candidates: Vec<String> = match (command, argument) {
+        (None, 0) if word.starts_with('/') => COMMAND_NAMES
+            .iter()
+            .map(|name| format!("/{name}"))
+            .collect(),
+        (Some("msg"), 1) => users.to_vec(),
+        (Some("react"), 1) => SHORTCODES
+            .iter()
+            .map(|(code, _)| format!(":{code}:"))
+            .collect(),
+        (Some("search"), _) if word.starts_with("from:") => {
+            users.iter().map(|user| format!("from:{user}")).collect()
+        }
+        (Some("search"), _) if word.starts_with("in:") => std::iter::once(LOBBY)
+            .chain(users.iter().map(String::as_str))
+            .map(|conversation| format!("in:{conversation}"))
+            .collect(),
+        _ if word.starts_with('@') => users.iter().map(|user| format!("@{user}")).collect(),
+        (Some("search"), _) => ["from:", "in:", "after:", "before:"]
+            .into_iter()
+            .map(String::from)
+            .chain(users.iter().cloned())
+            .collect(),
+        _ => users.to_vec(),
+    };
+    let word = word.to_lowercase();
+    candidates.retain(|candidate| candidate.to_lowercase().starts_with(&word));
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> Vec<String> {
+        ["alice", "albert", "bob"].map(String::from).to_vec()
+    }
+
+    /// Completes `input` with the cursor at its end, or at `|` if it has one.
+    fn start(input: &str) -> Option<Completion> {
+        let cursor = input.find('|').unwrap_or(input.len());
+        let input = Input::new(input.replace('|', "")).with_cursor(input[..cursor].chars().count());
+        Completion::start(&input, &users())
+    }
+
+    fn candidates(input: &str) -> Vec<String> {
+        start(input)
+            .map(|completion| completion.candidates)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn completes_commands_and_their_arguments() {
+        assert_eq!(candidates("/re"), ["/react"]);
+        assert_eq!(candidates("/msg al"), ["albert", "alice"]);
+        assert!(candidates("/react :tad").contains(&":tada:".to_string()));
+        assert_eq!(candidates("/search lunch from:b"), ["from:bob"]);
+        assert_eq!(candidates("/search in:l"), ["in:lobby"]);
+        assert_eq!(candidates("/search af"), ["after:"]);
+    }
+
+    #[test]
+    fn completes_nicknames_ignoring_case() {
+        assert_eq!(candidates("hi AL"), ["albert", "alice"]);
+        assert_eq!(candidates("hi @b"), ["@bob"]);
+        assert!(candidates("hi zed").is_empty());
+        // Nothing typed yet outside a command
+        assert!(candidates("hi ").is_empty());
+    }
+
+    #[test]
+    fn cycles_through_candidates() {
+        let mut completion = start("al").unwrap();
+        assert_eq!(completion.apply().value(), "albert ");
+        completion.cycle(1);
+        assert_eq!(completion.apply().value(), "alice ");
+        completion.cycle(1);
+        assert_eq!(completion.apply().value(), "albert ");
+        completion.cycle(-1);
+        assert_eq!(completion.apply().value(), "alice ");
+    }
+
+    #[test]
+    fn applies_in_the_middle_of_the_input() {
+        let completion = start("hi b| there").unwrap();
+        assert_eq!(completion.word_start(), 3);
+        let input = completion.apply();
+        assert_eq!(input.value(), "hi bob there");
+        assert_eq!(input.cursor(), 6);
+        let original = completion.original();
+        assert_eq!(original.value(), "hi b there");
+        assert_eq!(original.cursor(), 4);
+    }
+
+    #[test]
+    fn leaves_keys_open_for_their_value() {
+        let input = start("/search fr").unwrap().apply();
+        assert_eq!(input.value(), "/search from:");
+    }
+
+    #[test]
+    fn finds_the_word_after_wide_characters() {
+        let completion = start("héllo b").unwrap();
+        assert_eq!(completion.word_start(), 6);
+        assert_eq!(completion.apply().value(), "héllo bob ");
+    }
+}
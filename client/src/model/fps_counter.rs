@@ -1,6 +1,9 @@
 pub struct FpsCounter {
     frame_count: u64,
     last_tick: std::time::Instant,
+    /// Set when `fps` changed on its own, so the frame drawn to show it isn't counted.
+    /// Otherwise that frame changes `fps` again and an idle client never stops drawing.
+    refreshed: bool,
     pub fps: u64,
 }
 
@@ -9,15 +12,37 @@ impl FpsCounter {
         Self {
             frame_count: 0,
             last_tick: std::time::Instant::now(),
+            refreshed: false,
             fps: 0,
         }
     }
+    /// Counts a drawn frame, unless it was drawn to show a new `fps`.
     pub fn tick(&mut self) {
-        self.frame_count += 1;
-        if self.last_tick.elapsed().as_secs() >= 1 {
-            self.fps = self.frame_count;
-            self.frame_count = 0;
-            self.last_tick = std::time::Instant::now();
+        if !std::mem::take(&mut self.refreshed) {
+            self.frame_count += 1;
         }
+        // This frame already shows the new value
+        self.refresh();
+        self.refreshed = false;
+    }
+
+    /// Updates `fps` once a second has passed, even if nothing was drawn in it.
+    /// Returns whether it changed, in which case the next frame isn't counted.
+    pub fn refresh(&mut self) -> bool {
+        if self.last_tick.elapsed().as_secs() < 1 {
+            return false;
+        }
+        let changed = self.fps != self.frame_count;
+        self.fps = self.frame_count;
+        self.frame_count = 0;
+        self.last_tick = std::time::Instant::now();
+        self.refreshed = changed;
+        changed
+    }
+}
+
+impl Default for FpsCounter {
+    fn default() -> Self {
+        Self::new()
     }
 }
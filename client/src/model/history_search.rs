@@ -0,0 +1,26 @@
+use protocol::ChatMessage;
+
+/// Results of a server side history search, shown in an overlay.
+pub struct HistorySearch {
+    pub results: Vec<ChatMessage>,
+    pub selected: usize,
+}
+
+impl HistorySearch {
+    /// Opens the results with the newest one selected.
+    pub fn new(results: Vec<ChatMessage>) -> Self {
+        let selected = results.len().saturating_sub(1);
+        Self { results, selected }
+    }
+
+    pub fn move_selection(&mut self, offset: isize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(offset)
+            .min(self.results.len().saturating_sub(1));
+    }
+
+    pub fn selected(&self) -> Option<&ChatMessage> {
+        self.results.get(self.selected)
+    }
+}
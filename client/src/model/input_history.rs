@@ -0,0 +1,251 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use tracing::warn;
+use tui_input::Input;
+
+use crate::state_dir;
+
+/// Most entries kept, older ones are dropped when the history is loaded.
+const MAX_ENTRIES: usize = 1000;
+
+/// Messages and commands sent from the input, recalled with Up/Down and Ctrl-R. Stored per
+/// server as one JSON string per line, since entries can span several lines.
+pub struct InputHistory {
+    entries: Vec<String>,
+    /// The entry being shown while walking the history with Up/Down.
+    position: Option<usize>,
+    /// What was in the input before walking the history, restored past the newest entry.
+    draft: String,
+    path: Option<PathBuf>,
+    pub search: Option<ReverseSearch>,
+}
+
+/// Ctrl-R search through the history, newest match first.
+pub struct ReverseSearch {
+    pub query: Input,
+    /// Index of the entry currently matched.
+    pub found: Option<usize>,
+    /// The input from before the search, restored when it's cancelled.
+    pub draft: String,
+}
+
+impl InputHistory {
+    /// Loads the history for `server`, starting empty if there is none or it can't be read.
+    pub fn load(server: &str) -> Self {
+        let file_name: String = server
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path =
+            state_dir().map(|dir| dir.join("input_history").join(format!("{file_name}.jsonl")));
+        let mut history = Self {
+            entries: Vec::new(),
+            position: None,
+            draft: String::new(),
+            path,
+            search: None,
+        };
+        if let Err(e) = history.read() {
+            warn!("Failed to load the input history: {}", e);
+        }
+        history
+    }
+
+    fn read(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !path.exists() {
+            return Ok(());
+        }
+        for line in std::fs::read_to_string(path)?.lines() {
+            self.entries.push(serde_json::from_str(line)?);
+        }
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+            self.rewrite()?;
+        }
+        Ok(())
+    }
+
+    fn rewrite(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut contents = String::new();
+        for entry in &self.entries {
+            contents.push_str(&serde_json::to_string(entry)?);
+            contents.push('\n');
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Adds a sent message or command, unless it repeats the newest entry.
+    pub fn push(&mut self, entry: String) {
+        self.position = None;
+        if entry.trim().is_empty() || self.entries.last() == Some(&entry) {
+            return;
+        }
+        if let Err(e) = self.append(&entry) {
+            warn!("Failed to save the input history: {}", e);
+        }
+        self.entries.push(entry);
+    }
+
+    fn append(&self, entry: &str) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// The entry before the one shown, starting from the newest. `input` is kept as the
+    /// draft to come back to.
+    pub fn older(&mut self, input: &str) -> Option<&str> {
+        let position = match self.position {
+            Some(position) => position.checked_sub(1)?,
+            None => {
+                self.draft = input.to_string();
+                self.entries.len().checked_sub(1)?
+            }
+        };
+        self.position = Some(position);
+        Some(&self.entries[position])
+    }
+
+    /// The entry after the one shown, or the draft when moving past the newest entry.
+    pub fn newer(&mut self) -> Option<String> {
+        let position = self.position?;
+        if position + 1 < self.entries.len() {
+            self.position = Some(position + 1);
+            Some(self.entries[position + 1].clone())
+        } else {
+            self.position = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    /// Stops walking the history, e.g. because the recalled entry was edited.
+    pub fn reset_position(&mut self) {
+        self.position = None;
+    }
+
+    /// Index of the newest entry containing `query`, older than `before` if given.
+    fn find(&self, query: &str, before: Option<usize>) -> Option<usize> {
+        if query.is_empty() {
+            return None;
+        }
+        let end = before.unwrap_or(self.entries.len());
+        self.entries[..end]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+
+    /// Matches the newest entry again after the search query changed.
+    pub fn search_changed(&mut self) {
+        if let Some(search) = &self.search {
+            let found = self.find(search.query.value(), None);
+            self.search.as_mut().unwrap().found = found;
+        }
+    }
+
+    /// Moves the search to the next older match, staying on the current one if there is none.
+    pub fn search_older(&mut self) {
+        if let Some(search) = &self.search {
+            if let Some(older) = self.find(search.query.value(), search.found) {
+                self.search.as_mut().unwrap().found = Some(older);
+            }
+        }
+    }
+
+    /// The entry the search currently matches.
+    pub fn search_match(&self) -> Option<&str> {
+        let found = self.search.as_ref()?.found?;
+        Some(&self.entries[found])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A history of `entries` that isn't saved anywhere.
+    fn history(entries: &[&str]) -> InputHistory {
+        InputHistory {
+            entries: entries.iter().map(|entry| entry.to_string()).collect(),
+            position: None,
+            draft: String::new(),
+            path: None,
+            search: None,
+        }
+    }
+
+    fn search(history: &mut InputHistory, query: &str) -> Option<String> {
+        history.search = Some(ReverseSearch {
+            query: Input::new(query.to_string()),
+            found: None,
+            draft: String::new(),
+        });
+        history.search_changed();
+        history.search_match().map(str::to_string)
+    }
+
+    #[test]
+    fn walks_back_and_returns_to_the_draft() {
+        let mut history = history(&["one", "two"]);
+        assert_eq!(history.older("draft"), Some("two"));
+        assert_eq!(history.older("ignored"), Some("one"));
+        assert_eq!(history.older("ignored"), None);
+        assert_eq!(history.newer().as_deref(), Some("two"));
+        assert_eq!(history.newer().as_deref(), Some("draft"));
+        assert_eq!(history.newer(), None);
+    }
+
+    #[test]
+    fn empty_history_has_nothing_to_recall() {
+        let mut history = history(&[]);
+        assert_eq!(history.older("draft"), None);
+        assert_eq!(history.newer(), None);
+    }
+
+    #[test]
+    fn skips_blank_entries_and_repeats() {
+        let mut history = history(&["one"]);
+        history.push("one".to_string());
+        history.push("  ".to_string());
+        history.push("two".to_string());
+        history.push("one".to_string());
+        assert_eq!(history.entries, ["one", "two", "one"]);
+    }
+
+    #[test]
+    fn pushing_starts_from_the_newest_again() {
+        let mut history = history(&["one", "two"]);
+        history.older("");
+        history.older("");
+        history.push("three".to_string());
+        assert_eq!(history.older(""), Some("three"));
+    }
+
+    #[test]
+    fn searches_newest_first() {
+        let mut history = history(&["git push", "ls", "git pull", "cd"]);
+        assert_eq!(search(&mut history, "git").as_deref(), Some("git pull"));
+        history.search_older();
+        assert_eq!(history.search_match(), Some("git push"));
+        // Stays on the oldest match
+        history.search_older();
+        assert_eq!(history.search_match(), Some("git push"));
+        assert_eq!(search(&mut history, "svn"), None);
+        assert_eq!(search(&mut history, ""), None);
+    }
+}
@@ -0,0 +1,30 @@
+/// The numbered list of recent links, shown in an overlay to copy or open one of them.
+pub struct LinkPicker {
+    /// Newest first.
+    pub links: Vec<String>,
+    pub selected: usize,
+}
+
+impl LinkPicker {
+    pub fn new(links: Vec<String>) -> Self {
+        Self { links, selected: 0 }
+    }
+
+    pub fn move_selection(&mut self, offset: isize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(offset)
+            .min(self.links.len().saturating_sub(1));
+    }
+
+    /// Selects link `number`, counting from 1 like the list shows.
+    pub fn select_number(&mut self, number: usize) {
+        if (1..=self.links.len()).contains(&number) {
+            self.selected = number - 1;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.links.get(self.selected).map(String::as_str)
+    }
+}
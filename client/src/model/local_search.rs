@@ -0,0 +1,67 @@
+use std::ops::Range;
+
+use tui_input::Input;
+
+/// Incremental search over the lines of the active tab, started with `/` in normal mode.
+#[derive(Default)]
+pub struct LocalSearch {
+    pub input: Input,
+    /// Whether key presses go to the search input.
+    pub editing: bool,
+    /// Hide the lines that don't match.
+    pub filter: bool,
+}
+
+impl LocalSearch {
+    pub fn query(&self) -> &str {
+        self.input.value()
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.query().is_empty()
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        self.is_active() && !find_matches(text, self.query()).is_empty()
+    }
+
+    /// Whether a line should be shown, i.e. it matches or filtering is off.
+    pub fn shows(&self, text: &str) -> bool {
+        !self.filter || !self.is_active() || self.matches(text)
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Byte ranges of the case insensitive, non-overlapping occurrences of `query` in `text`.
+pub fn find_matches(text: &str, query: &str) -> Vec<Range<usize>> {
+    let lowercase = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let query: Vec<char> = query.chars().map(lowercase).collect();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut matches = Vec::new();
+    if query.is_empty() {
+        return matches;
+    }
+
+    let mut i = 0;
+    while i + query.len() <= chars.len() {
+        let candidate = &chars[i..i + query.len()];
+        if candidate
+            .iter()
+            .zip(&query)
+            .all(|((_, c), q)| lowercase(*c) == *q)
+        {
+            let start = chars[i].0;
+            let end = chars
+                .get(i + query.len())
+                .map_or(text.len(), |(idx, _)| *idx);
+            matches.push(start..end);
+            i += query.len();
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
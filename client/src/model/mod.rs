@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod model;
 pub use model::InputMode;
 pub use model::Model;
@@ -5,3 +6,29 @@ pub use model::Model;
 pub mod fps_counter;
 pub use fps_counter::FpsCounter;
 
+pub mod chat_entry;
+pub use chat_entry::ChatEntry;
+
+pub mod typing;
+pub use typing::Typing;
+
+pub mod history_search;
+pub use history_search::HistorySearch;
+
+pub mod local_search;
+pub use local_search::LocalSearch;
+
+pub mod click_areas;
+pub use click_areas::ClickAreas;
+
+pub mod input_history;
+pub use input_history::InputHistory;
+
+pub mod completion;
+pub use completion::Completion;
+
+pub mod vi;
+pub use vi::Vi;
+
+pub mod link_picker;
+pub use link_picker::LinkPicker;
@@ -1,16 +1,24 @@
-use ratatui::widgets::ListItem;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use protocol::{ChatMessage, MessageId, Notice, LOBBY};
+use tracing::Level;
 use tui_input::Input;
 
-use crate::{FpsCounter, Message, NetworkManager, Tui};
+use crate::{
+    links::find_urls, ChatEntry, ClickAreas, Completion, Config, FpsCounter, HistorySearch,
+    InputHistory, Keymap, LinkPicker, LocalSearch, LogEntry, Message, NetworkManager, Theme, Tui,
+    Typing, Vi,
+};
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum InputMode {
     Normal,
     Editing,
 }
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ActiveTab {
     Chat,
+    Mentions,
     Logs,
 }
 
@@ -18,26 +26,93 @@ impl ActiveTab {
     pub fn get_idx(&self) -> usize {
         match self {
             ActiveTab::Chat => 0,
-            ActiveTab::Logs => 1,
+            ActiveTab::Mentions => 1,
+            ActiveTab::Logs => 2,
+        }
+    }
+
+    /// The tab after this one, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            ActiveTab::Chat => ActiveTab::Mentions,
+            ActiveTab::Mentions => ActiveTab::Logs,
+            ActiveTab::Logs => ActiveTab::Chat,
         }
     }
 }
 
 // Model state
-pub struct Model<'a> {
+pub struct Model {
     pub message_tx: tokio::sync::mpsc::UnboundedSender<Message>,
     pub fps_counter: FpsCounter,
     pub input: Input,
     pub input_mode: InputMode,
-    pub messages: Vec<String>,
+    pub messages: Vec<ChatEntry>,
     pub network_manager: NetworkManager,
     pub active_tab: ActiveTab,
-    pub logs: Vec<ListItem<'a>>,
+    /// The most recent log lines, at most `config.log_buffer_size` of them.
+    pub logs: VecDeque<LogEntry>,
+    /// The log line highlighted by jumping between search matches.
+    pub log_selected: Option<usize>,
+    /// Least severe level shown in the Logs tab.
+    pub log_level: Level,
     pub is_user_registered: bool,
+    /// Set once the recent messages sent on joining have all arrived.
+    pub history_loaded: bool,
+    /// Why the server refused the name last registered with, shown until the next try.
+    pub register_error: Option<String>,
+    pub username: String,
+    /// The message currently being edited in the input box, if any.
+    pub editing: Option<MessageId>,
+    /// The message highlighted in the chat view while navigating in normal mode.
+    pub selected: Option<MessageId>,
+    /// The first row shown in the chat view after scrolling it with the mouse. `None` shows
+    /// the selected message, or the newest ones.
+    pub chat_scroll: Option<usize>,
+    /// The message the next sent message replies to.
+    pub replying_to: Option<MessageId>,
+    /// Root of the thread shown in the chat view, `None` shows every message.
+    pub thread: Option<MessageId>,
+    /// Messages shown as they were typed, without their markup formatted.
+    pub raw_messages: HashSet<MessageId>,
+    pub typing: Typing,
+    /// The last read message of each conversation.
+    pub read_markers: HashMap<String, MessageId>,
+    /// Whether `read_markers` changed since they were last sent to the server.
+    pub read_markers_changed: bool,
+    /// The "new messages" divider is drawn after this message.
+    pub unread_divider: Option<MessageId>,
+    /// Open history search results overlay.
+    pub history_search: Option<HistorySearch>,
+    /// Open overlay listing recent links.
+    pub link_picker: Option<LinkPicker>,
+    pub local_search: LocalSearch,
+    /// The mention highlighted in the Mentions tab.
+    pub mention_selected: Option<MessageId>,
+    /// Mentions that arrived since the Mentions tab was last opened.
+    pub unread_mentions: usize,
+    /// Whether anything changed since the last draw.
+    pub dirty: bool,
+    /// Whether the terminal has focus, as far as it reports focus changes.
+    pub focused: bool,
+    pub click_areas: ClickAreas,
+    pub input_history: InputHistory,
+    pub completion: Option<Completion>,
+    pub keymap: Keymap,
+    pub vi: Vi,
+    pub theme: Theme,
+    pub config: Config,
 }
 
-impl<'a> Model<'a> {
-    pub fn new(tui: &Tui, network_manager: NetworkManager) -> Self {
+impl Model {
+    pub fn new(
+        tui: &Tui,
+        network_manager: NetworkManager,
+        config: Config,
+        input_history: InputHistory,
+        keymap: Keymap,
+        theme: Theme,
+    ) -> Self {
         Self {
             message_tx: tui.event_tx.clone(),
             fps_counter: FpsCounter::new(),
@@ -46,8 +121,414 @@ impl<'a> Model<'a> {
             messages: Vec::new(),
             network_manager,
             active_tab: ActiveTab::Chat,
-            logs: Vec::new(),
+            logs: VecDeque::with_capacity(config.log_buffer_size),
+            log_selected: None,
+            log_level: Level::TRACE,
             is_user_registered: false,
+            history_loaded: false,
+            register_error: None,
+            username: String::new(),
+            editing: None,
+            selected: None,
+            chat_scroll: None,
+            replying_to: None,
+            thread: None,
+            raw_messages: HashSet::new(),
+            typing: Typing::new(),
+            read_markers: HashMap::new(),
+            read_markers_changed: false,
+            unread_divider: None,
+            history_search: None,
+            link_picker: None,
+            local_search: LocalSearch::default(),
+            mention_selected: None,
+            unread_mentions: 0,
+            dirty: true,
+            focused: true,
+            click_areas: ClickAreas::default(),
+            input_history,
+            completion: None,
+            keymap,
+            vi: Vi::default(),
+            theme,
+            config,
+        }
+    }
+
+    pub fn find_message(&self, id: MessageId) -> Option<&ChatMessage> {
+        self.messages.iter().rev().find_map(|entry| match entry {
+            ChatEntry::Message(message) if message.id == id => Some(message),
+            _ => None,
+        })
+    }
+
+    pub fn find_message_mut(&mut self, id: MessageId) -> Option<&mut ChatMessage> {
+        self.messages
+            .iter_mut()
+            .rev()
+            .find_map(|entry| match entry {
+                ChatEntry::Message(message) if message.id == id => Some(message),
+                _ => None,
+            })
+    }
+
+    /// The most recent message this user sent to everyone that hasn't been deleted.
+    pub fn last_own_message(&self) -> Option<&ChatMessage> {
+        self.messages.iter().rev().find_map(|entry| match entry {
+            ChatEntry::Message(message)
+                if message.author == self.username && !message.deleted && message.to.is_none() =>
+            {
+                Some(message)
+            }
+            _ => None,
+        })
+    }
+
+    /// Everyone else who wrote or was sent a message we know of, sorted by name.
+    pub fn known_users(&self) -> Vec<String> {
+        let mut users: Vec<String> = self
+            .messages
+            .iter()
+            .filter_map(|entry| match entry {
+                ChatEntry::Message(message) => Some(message),
+                ChatEntry::Notice(_) => None,
+            })
+            .flat_map(|message| std::iter::once(&message.author).chain(&message.to))
+            .filter(|user| **user != self.username)
+            .cloned()
+            .collect();
+        users.sort();
+        users.dedup();
+        users
+    }
+
+    /// The links in the newest messages, newest first and without repeats.
+    pub fn recent_links(&self) -> Vec<String> {
+        const MAX_LINKS: usize = 9;
+        let mut links: Vec<String> = Vec::new();
+        for entry in self.messages.iter().rev() {
+            let ChatEntry::Message(message) = entry else {
+                continue;
+            };
+            for url in find_urls(&message.body).into_iter().rev() {
+                let link = &message.body[url];
+                if !links.iter().any(|other| other == link) {
+                    links.push(link.to_string());
+                }
+                if links.len() == MAX_LINKS {
+                    return links;
+                }
+            }
+        }
+        links
+    }
+
+    /// Whether someone else's `message` contains your name or one of the highlight words,
+    /// as a whole word and ignoring case.
+    pub fn is_mention(&self, message: &ChatMessage) -> bool {
+        if message.author == self.username || message.deleted {
+            return false;
         }
+        let body = message.body.to_lowercase();
+        std::iter::once(&self.username)
+            .chain(&self.config.highlight_words)
+            .filter(|word| !word.is_empty())
+            .any(|word| contains_word(&body, &word.to_lowercase()))
+    }
+
+    /// Every loaded message that mentions you, oldest first.
+    pub fn mentions(&self) -> Vec<&ChatEntry> {
+        self.messages
+            .iter()
+            .filter(
+                |entry| matches!(entry, ChatEntry::Message(message) if self.is_mention(message)),
+            )
+            .collect()
+    }
+
+    /// Moves the selection in the Mentions tab, like `move_selection` in the chat view.
+    pub fn move_mention_selection(&mut self, offset: isize) {
+        let ids: Vec<MessageId> = self
+            .mentions()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                ChatEntry::Message(message) => Some(message.id),
+                ChatEntry::Notice(_) => None,
+            })
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+        let current = self
+            .mention_selected
+            .and_then(|selected| ids.iter().position(|id| *id == selected));
+        let idx = match current {
+            Some(idx) => idx.saturating_add_signed(offset).min(ids.len() - 1),
+            None if offset < 0 => ids.len() - 1,
+            None => return,
+        };
+        self.mention_selected = Some(ids[idx]);
+    }
+
+    /// The newest message to everyone that hasn't been deleted.
+    pub fn last_message(&self) -> Option<&ChatMessage> {
+        self.messages.iter().rev().find_map(|entry| match entry {
+            ChatEntry::Message(message) if !message.deleted && message.to.is_none() => {
+                Some(message)
+            }
+            _ => None,
+        })
+    }
+
+    /// Whether `id` is `root` or a (possibly indirect) reply to it.
+    pub fn is_in_thread(&self, id: MessageId, root: MessageId) -> bool {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            if id == root {
+                return true;
+            }
+            current = self.find_message(id).and_then(|message| message.reply_to);
+        }
+        false
+    }
+
+    /// The entries shown in the chat view, limited to the open thread if there is one
+    /// and to search matches while filtering.
+    pub fn visible_entries(&self) -> Vec<&ChatEntry> {
+        self.messages
+            .iter()
+            .filter(|entry| match (self.thread, entry) {
+                (Some(root), ChatEntry::Message(message)) => self.is_in_thread(message.id, root),
+                (Some(_), ChatEntry::Notice(_)) => false,
+                (None, _) => true,
+            })
+            .filter(|entry| self.local_search.shows(&entry.text()))
+            .collect()
+    }
+
+    /// The indices of the log lines shown in the Logs tab, oldest first.
+    pub fn visible_logs(&self) -> Vec<usize> {
+        self.logs
+            .iter()
+            .enumerate()
+            .filter(|(_, log)| log.level <= self.log_level)
+            .filter(|(_, log)| self.local_search.shows(&log.text()))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Selects the next (or previous) line in the active tab that matches the local search,
+    /// wrapping around at the ends.
+    pub fn jump_to_match(&mut self, forward: bool) {
+        match self.active_tab {
+            ActiveTab::Chat => {
+                let matches: Vec<MessageId> = self
+                    .visible_entries()
+                    .into_iter()
+                    .filter_map(|entry| match entry {
+                        ChatEntry::Message(message) if self.local_search.matches(&entry.text()) => {
+                            Some(message.id)
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                self.selected = next_match(&matches, self.selected, forward);
+            }
+            ActiveTab::Logs => {
+                let matches: Vec<usize> = self
+                    .visible_logs()
+                    .into_iter()
+                    .filter(|idx| self.local_search.matches(&self.logs[*idx].text()))
+                    .collect();
+                self.log_selected = next_match(&matches, self.log_selected, forward);
+            }
+            ActiveTab::Mentions => {}
+        }
+    }
+
+    /// Moves the selection `offset` messages down (positive) or up (negative) the chat view.
+    /// Moving up with nothing selected selects the newest message.
+    pub fn move_selection(&mut self, offset: isize) {
+        let ids: Vec<MessageId> = self
+            .visible_entries()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                ChatEntry::Message(message) => Some(message.id),
+                ChatEntry::Notice(_) => None,
+            })
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+        let current = self
+            .selected
+            .and_then(|selected| ids.iter().position(|id| *id == selected));
+        let idx = match current {
+            Some(idx) => idx.saturating_add_signed(offset).min(ids.len() - 1),
+            None if offset < 0 => ids.len() - 1,
+            None => return,
+        };
+        self.selected = Some(ids[idx]);
+    }
+
+    /// Scrolls the chat view by `offset` notches of the scroll wheel, down (positive) or up
+    /// (negative). Scrolling down to the newest message clears the selection to follow new
+    /// messages again.
+    pub fn scroll_chat(&mut self, offset: isize) {
+        const ROWS_PER_NOTCH: isize = 3;
+        // Several scroll events can come in before the next frame updates `chat_rows`
+        let (top, max_top) = self.click_areas.chat_rows;
+        let top = self
+            .chat_scroll
+            .map_or(top, |scroll| scroll.min(max_top))
+            .saturating_add_signed(offset * ROWS_PER_NOTCH)
+            .min(max_top);
+        if top == max_top && offset > 0 {
+            self.chat_scroll = None;
+            self.selected = None;
+        } else {
+            self.chat_scroll = Some(top);
+        }
+    }
+
+    /// Scrolls the Logs tab by moving the selection, clearing it when scrolling down past
+    /// the newest line.
+    pub fn scroll_logs(&mut self, offset: isize) {
+        let visible_logs = self.visible_logs();
+        let Some(last) = visible_logs.len().checked_sub(1) else {
+            return;
+        };
+        let current = self
+            .log_selected
+            .and_then(|selected| visible_logs.iter().position(|idx| *idx == selected));
+        self.log_selected = match current {
+            Some(idx) if offset > 0 && idx == last => None,
+            Some(idx) => Some(visible_logs[idx.saturating_add_signed(offset).min(last)]),
+            None if offset < 0 => Some(visible_logs[last]),
+            None => None,
+        };
+    }
+
+    /// Marks every lobby message up to `id` as read.
+    pub fn mark_read(&mut self, id: MessageId) {
+        let marker = self.read_markers.entry(LOBBY.to_string()).or_default();
+        if id > *marker {
+            *marker = id;
+            self.read_markers_changed = true;
+        }
+    }
+
+    pub fn last_read(&self) -> MessageId {
+        self.read_markers.get(LOBBY).copied().unwrap_or_default()
+    }
+
+    /// Number of messages from other users that arrived after the last read one.
+    pub fn unread_count(&self) -> usize {
+        let last_read = self.last_read();
+        self.messages
+            .iter()
+            .filter(|entry| {
+                matches!(entry, ChatEntry::Message(message)
+                    if message.id > last_read && !message.deleted && message.author != self.username)
+            })
+            .count()
+    }
+
+    /// Called when the chat becomes visible, placing the divider where the user left off.
+    pub fn open_chat(&mut self) {
+        self.unread_divider = (self.unread_count() > 0).then(|| self.last_read());
+        if let Some(message) = self.messages.iter().rev().find_map(|entry| match entry {
+            ChatEntry::Message(message) => Some(message.id),
+            ChatEntry::Notice(_) => None,
+        }) {
+            self.mark_read(message);
+        }
+    }
+
+    /// Selects `message` in the chat view, adding it in order of its id if it isn't loaded.
+    pub fn jump_to(&mut self, message: ChatMessage) {
+        let id = message.id;
+        if self.find_message(id).is_none() {
+            let idx = self
+                .messages
+                .iter()
+                .position(|entry| matches!(entry, ChatEntry::Message(other) if other.id > id))
+                .unwrap_or(self.messages.len());
+            self.messages.insert(idx, ChatEntry::Message(message));
+        }
+        self.active_tab = ActiveTab::Chat;
+        self.thread = None;
+        self.selected = Some(id);
+        self.input_mode = InputMode::Normal;
+    }
+
+    /// Adds a line to the log buffer, dropping the oldest one when it's full.
+    pub fn push_log(&mut self, log: LogEntry) {
+        if self.logs.len() >= self.config.log_buffer_size {
+            self.logs.pop_front();
+            self.log_selected = self.log_selected.and_then(|idx| idx.checked_sub(1));
+        }
+        self.logs.push_back(log);
+    }
+
+    /// Adds a client side notice to the chat view.
+    pub fn push_notice(&mut self, text: impl Into<String>) {
+        self.messages.push(ChatEntry::Notice(Notice::new(text)));
+    }
+}
+
+/// Whether `word` appears in `text` on its own, not as part of a longer word.
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    })
+}
+
+/// The match after (or before) `current` in `matches`, which is in display order.
+fn next_match<T: Copy + PartialOrd>(matches: &[T], current: Option<T>, forward: bool) -> Option<T> {
+    let (first, last) = (matches.first()?, matches.last()?);
+    let Some(current) = current else {
+        return Some(if forward { *first } else { *last });
+    };
+    if forward {
+        matches
+            .iter()
+            .find(|m| **m > current)
+            .or(Some(first))
+            .copied()
+    } else {
+        matches
+            .iter()
+            .rev()
+            .find(|m| **m < current)
+            .or(Some(last))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_whole_words_only() {
+        assert!(contains_word("hi ann", "ann"));
+        assert!(contains_word("ann: look", "ann"));
+        assert!(contains_word("@ann, hi", "ann"));
+        assert!(contains_word("ping ann!", "ann"));
+        assert!(!contains_word("hi anna", "ann"));
+        assert!(!contains_word("joann said", "ann"));
+        assert!(!contains_word("ann_b is here", "ann"));
+        assert!(contains_word("joann and ann", "ann"));
+    }
+
+    #[test]
+    fn words_end_at_any_alphabet() {
+        assert!(contains_word("ça va, zoé?", "zoé"));
+        assert!(!contains_word("zoééé", "zoé"));
+        assert!(!contains_word("éann", "ann"));
     }
 }
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two typing-start events sent to the server.
+const SEND_INTERVAL: Duration = Duration::from_secs(3);
+/// How long after the last keystroke this user counts as typing.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long another user is shown as typing without hearing from them again.
+const DISPLAY_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// Typing indicator state, both for this user and for the other users in the chat.
+pub struct Typing {
+    users: HashMap<String, Instant>,
+    sent_at: Option<Instant>,
+    last_edit: Instant,
+}
+
+impl Typing {
+    pub fn new() -> Self {
+        Self {
+            users: HashMap::new(),
+            sent_at: None,
+            last_edit: Instant::now(),
+        }
+    }
+
+    /// Records a change to the input, returning whether a typing-start event should be sent.
+    pub fn input_changed(&mut self) -> bool {
+        let now = Instant::now();
+        self.last_edit = now;
+        match self.sent_at {
+            Some(sent_at) if now.duration_since(sent_at) < SEND_INTERVAL => false,
+            _ => {
+                self.sent_at = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Marks this user as no longer typing, returning whether a typing-stop event should be sent.
+    pub fn stop(&mut self) -> bool {
+        self.sent_at.take().is_some()
+    }
+
+    /// Whether this user stopped typing a while ago without sending or clearing the input.
+    pub fn is_idle(&self) -> bool {
+        self.sent_at.is_some() && self.last_edit.elapsed() >= IDLE_TIMEOUT
+    }
+
+    pub fn set_user_typing(&mut self, user: String, active: bool) {
+        if active {
+            self.users.insert(user, Instant::now());
+        } else {
+            self.users.remove(&user);
+        }
+    }
+
+    /// Forgets users we haven't heard from in a while, e.g. because they disconnected.
+    /// Returns whether anyone was forgotten.
+    pub fn expire(&mut self) -> bool {
+        let count = self.users.len();
+        self.users
+            .retain(|_, last_seen| last_seen.elapsed() < DISPLAY_TIMEOUT);
+        self.users.len() != count
+    }
+
+    /// The other users currently typing, sorted by name.
+    pub fn users(&self) -> Vec<&str> {
+        let mut users: Vec<&str> = self.users.keys().map(String::as_str).collect();
+        users.sort_unstable();
+        users
+    }
+}
+
+impl Default for Typing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
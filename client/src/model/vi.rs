@@ -0,0 +1,361 @@
+use tui_input::Input;
+
+/// Operators waiting for a motion, like the `d` in `dw`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+/// What a key did to the input in vi normal mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViOutcome {
+    Handled,
+    /// The key enters editing mode, with the cursor already in place.
+    StartEditing,
+    /// Not a vi key, so it's handled like any other normal mode key.
+    Ignored,
+}
+
+/// Vi style editing of the chat input in normal mode.
+#[derive(Default)]
+pub struct Vi {
+    pub pending: Option<Operator>,
+    /// Text from the last delete, change or yank, put back with `p` and `P`.
+    pub register: String,
+}
+
+impl Vi {
+    pub fn key(&mut self, input: &mut Input, key: char) -> ViOutcome {
+        let chars: Vec<char> = input.value().chars().collect();
+        let cursor = input.cursor().min(chars.len());
+
+        if let Some(operator) = self.pending.take() {
+            let (line_start, line_end) = line_bounds(&chars, cursor);
+            let whole_line = matches!(
+                (operator, key),
+                (Operator::Delete, 'd') | (Operator::Change, 'c') | (Operator::Yank, 'y')
+            );
+            let range = if whole_line {
+                // `dd` takes a line break with it, the others leave an empty line
+                match operator {
+                    Operator::Delete if line_end < chars.len() => (line_start, line_end + 1),
+                    Operator::Delete if line_start > 0 => (line_start - 1, line_end),
+                    _ => (line_start, line_end),
+                }
+            } else {
+                match motion(&chars, cursor, key) {
+                    Some(_) if key == '$' => (cursor, line_end),
+                    // `cw` leaves the space after the word, like `ce`
+                    Some(_) if key == 'w' && operator == Operator::Change => {
+                        (cursor, word_end(&chars, cursor).min(line_end))
+                    }
+                    // Motions stop at the end of the line
+                    Some(target) if key == 'w' => (cursor, target.min(line_end.max(cursor + 1))),
+                    Some(target) => (cursor.min(target), cursor.max(target)),
+                    None => return ViOutcome::Handled,
+                }
+            };
+            if range.0 == range.1 {
+                // Nothing to take, but a change still starts editing, e.g. `cc` on an empty line
+                let changes = operator == Operator::Change && (whole_line || key == '$');
+                return if changes {
+                    ViOutcome::StartEditing
+                } else {
+                    ViOutcome::Handled
+                };
+            }
+            self.register = chars[range.0..range.1].iter().collect();
+            if operator == Operator::Yank {
+                *input = Input::new(input.value().to_string()).with_cursor(range.0);
+                return ViOutcome::Handled;
+            }
+            let mut chars = chars;
+            chars.drain(range.0..range.1);
+            let cursor = if whole_line && operator == Operator::Delete {
+                line_bounds(&chars, range.0.min(chars.len())).0
+            } else {
+                range.0
+            };
+            *input = Input::new(chars.into_iter().collect()).with_cursor(cursor);
+            if operator == Operator::Change {
+                return ViOutcome::StartEditing;
+            }
+            self.clamp(input);
+            return ViOutcome::Handled;
+        }
+
+        let (line_start, line_end) = line_bounds(&chars, cursor);
+        let value = input.value().to_string();
+        match key {
+            'd' => self.pending = Some(Operator::Delete),
+            'c' => self.pending = Some(Operator::Change),
+            'y' => self.pending = Some(Operator::Yank),
+            'x' if cursor < line_end => {
+                self.register = chars[cursor].to_string();
+                let mut chars = chars;
+                chars.remove(cursor);
+                *input = Input::new(chars.into_iter().collect()).with_cursor(cursor);
+                self.clamp(input);
+            }
+            'p' | 'P' if !self.register.is_empty() => {
+                let at = if key == 'p' && cursor < line_end {
+                    cursor + 1
+                } else {
+                    cursor
+                };
+                let mut chars = chars;
+                chars.splice(at..at, self.register.chars());
+                let last = at + self.register.chars().count() - 1;
+                *input = Input::new(chars.into_iter().collect()).with_cursor(last);
+            }
+            'i' => return ViOutcome::StartEditing,
+            'a' => {
+                *input = Input::new(value).with_cursor((cursor + 1).min(line_end));
+                return ViOutcome::StartEditing;
+            }
+            'A' => {
+                *input = Input::new(value).with_cursor(line_end);
+                return ViOutcome::StartEditing;
+            }
+            'I' => {
+                *input = Input::new(value).with_cursor(line_start);
+                return ViOutcome::StartEditing;
+            }
+            _ => match motion(&chars, cursor, key) {
+                Some(target) => {
+                    *input = Input::new(value).with_cursor(target);
+                    self.clamp(input);
+                }
+                None => return ViOutcome::Ignored,
+            },
+        }
+        ViOutcome::Handled
+    }
+
+    /// Keeps the cursor on a character, as normal mode has no position past the end of a line.
+    pub fn clamp(&self, input: &mut Input) {
+        let chars: Vec<char> = input.value().chars().collect();
+        let cursor = input.cursor().min(chars.len());
+        let (line_start, line_end) = line_bounds(&chars, cursor);
+        if cursor == line_end && cursor > line_start {
+            *input = Input::new(input.value().to_string()).with_cursor(cursor - 1);
+        }
+    }
+}
+
+/// Where `key` moves the cursor to, `None` if it isn't a motion.
+fn motion(chars: &[char], cursor: usize, key: char) -> Option<usize> {
+    let (line_start, line_end) = line_bounds(chars, cursor);
+    Some(match key {
+        'h' => cursor.saturating_sub(1).max(line_start),
+        'l' => (cursor + 1).min(line_end),
+        '0' => line_start,
+        '$' => line_end.saturating_sub(1).max(line_start),
+        'w' => {
+            let mut idx = cursor;
+            if idx < chars.len() {
+                let class = char_class(chars[idx]);
+                while idx < chars.len() && char_class(chars[idx]) == class && class != 0 {
+                    idx += 1;
+                }
+            }
+            while idx < chars.len() && char_class(chars[idx]) == 0 {
+                idx += 1;
+            }
+            idx
+        }
+        'b' => {
+            let mut idx = cursor;
+            while idx > 0 && char_class(chars[idx - 1]) == 0 {
+                idx -= 1;
+            }
+            if idx > 0 {
+                let class = char_class(chars[idx - 1]);
+                while idx > 0 && char_class(chars[idx - 1]) == class {
+                    idx -= 1;
+                }
+            }
+            idx
+        }
+        _ => return None,
+    })
+}
+
+/// Where the word at `cursor` ends, or the next one if `cursor` is on whitespace.
+fn word_end(chars: &[char], cursor: usize) -> usize {
+    let mut idx = cursor;
+    while idx < chars.len() && char_class(chars[idx]) == 0 {
+        idx += 1;
+    }
+    if idx < chars.len() {
+        let class = char_class(chars[idx]);
+        while idx < chars.len() && char_class(chars[idx]) == class {
+            idx += 1;
+        }
+    }
+    idx
+}
+
+/// Start of the line `cursor` is on, and the position of its line break or the end.
+fn line_bounds(chars: &[char], cursor: usize) -> (usize, usize) {
+    let start = chars[..cursor]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |idx| idx + 1);
+    let end = chars[cursor..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(chars.len(), |idx| cursor + idx);
+    (start, end)
+}
+
+/// Words are runs of characters of the same class: whitespace, word characters or
+/// punctuation.
+fn char_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Presses `keys` on `text` with the cursor at `cursor`, returning the text, the cursor
+    /// and the outcome of the last key.
+    fn press(vi: &mut Vi, text: &str, cursor: usize, keys: &str) -> (String, usize, ViOutcome) {
+        let mut input = Input::new(text.to_string()).with_cursor(cursor);
+        let mut outcome = ViOutcome::Ignored;
+        for key in keys.chars() {
+            outcome = vi.key(&mut input, key);
+        }
+        (input.value().to_string(), input.cursor(), outcome)
+    }
+
+    fn cursor_after(text: &str, cursor: usize, keys: &str) -> usize {
+        press(&mut Vi::default(), text, cursor, keys).1
+    }
+
+    #[test]
+    fn motions() {
+        let text = "foo bar.baz\nqux";
+        assert_eq!(cursor_after(text, 4, "h"), 3);
+        assert_eq!(cursor_after(text, 4, "l"), 5);
+        assert_eq!(cursor_after(text, 0, "h"), 0);
+        assert_eq!(cursor_after(text, 10, "l"), 10);
+        assert_eq!(cursor_after(text, 6, "0"), 0);
+        assert_eq!(cursor_after(text, 0, "$"), 10);
+        assert_eq!(cursor_after(text, 13, "0"), 12);
+        assert_eq!(cursor_after(text, 0, "w"), 4);
+        assert_eq!(cursor_after(text, 4, "w"), 7);
+        assert_eq!(cursor_after(text, 8, "w"), 12);
+        assert_eq!(cursor_after(text, 8, "b"), 7);
+        assert_eq!(cursor_after(text, 4, "b"), 0);
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        assert_eq!(
+            press(&mut Vi::default(), "foo", 0, "z").2,
+            ViOutcome::Ignored
+        );
+    }
+
+    #[test]
+    fn x_deletes_the_character_under_the_cursor() {
+        let mut vi = Vi::default();
+        assert_eq!(
+            press(&mut vi, "abc", 2, "x"),
+            ("ab".to_string(), 1, ViOutcome::Handled)
+        );
+        assert_eq!(vi.register, "c");
+    }
+
+    #[test]
+    fn dd_deletes_the_line() {
+        let mut vi = Vi::default();
+        assert_eq!(press(&mut vi, "a\nbb\nc", 3, "dd").0, "a\nc");
+        assert_eq!(vi.register, "bb\n");
+        assert_eq!(
+            press(&mut vi, "a\nbb", 3, "dd"),
+            ("a".to_string(), 0, ViOutcome::Handled)
+        );
+        assert_eq!(press(&mut vi, "abc", 1, "dd").0, "");
+    }
+
+    #[test]
+    fn dw_and_d_dollar_stop_at_the_line_end() {
+        let mut vi = Vi::default();
+        assert_eq!(press(&mut vi, "foo bar\nbaz", 4, "dw").0, "foo \nbaz");
+        assert_eq!(press(&mut vi, "foo bar\nbaz", 4, "d$").0, "foo \nbaz");
+        assert_eq!(vi.register, "bar");
+    }
+
+    #[test]
+    fn empty_ranges_leave_the_input_and_register_alone() {
+        let mut vi = Vi {
+            register: "kept".to_string(),
+            ..Vi::default()
+        };
+        assert_eq!(
+            press(&mut vi, "a\n\nb", 2, "d$"),
+            ("a\n\nb".to_string(), 2, ViOutcome::Handled)
+        );
+        assert_eq!(press(&mut vi, "a\n\nb", 2, "y$").0, "a\n\nb");
+        assert_eq!(press(&mut vi, "ab\ncd", 3, "dh").0, "ab\ncd");
+        assert_eq!(vi.register, "kept");
+        assert_eq!(
+            press(&mut vi, "a\n\nb", 2, "c$"),
+            ("a\n\nb".to_string(), 2, ViOutcome::StartEditing)
+        );
+        assert_eq!(vi.register, "kept");
+    }
+
+    #[test]
+    fn cw_changes_to_the_end_of_the_word() {
+        let mut vi = Vi::default();
+        assert_eq!(
+            press(&mut vi, "foo bar", 0, "cw"),
+            (" bar".to_string(), 0, ViOutcome::StartEditing)
+        );
+        assert_eq!(vi.register, "foo");
+        assert_eq!(press(&mut vi, "foo bar", 3, "cw").0, "foo");
+    }
+
+    #[test]
+    fn cc_clears_the_line_and_starts_editing() {
+        assert_eq!(
+            press(&mut Vi::default(), "a\nbc\nd", 3, "cc"),
+            ("a\n\nd".to_string(), 2, ViOutcome::StartEditing)
+        );
+    }
+
+    #[test]
+    fn yank_and_put() {
+        let mut vi = Vi::default();
+        assert_eq!(
+            press(&mut vi, "foo bar", 0, "yw"),
+            ("foo bar".to_string(), 0, ViOutcome::Handled)
+        );
+        assert_eq!(vi.register, "foo ");
+        assert_eq!(
+            press(&mut vi, "ab", 0, "p"),
+            ("afoo b".to_string(), 4, ViOutcome::Handled)
+        );
+        assert_eq!(
+            press(&mut vi, "ab", 0, "P"),
+            ("foo ab".to_string(), 3, ViOutcome::Handled)
+        );
+    }
+
+    #[test]
+    fn put_with_an_empty_register_does_nothing() {
+        assert_eq!(press(&mut Vi::default(), "ab", 0, "p").0, "ab");
+    }
+}
@@ -1,15 +1,16 @@
 use anyhow::Result;
+use protocol::{ClientMessage, ServerMessage};
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
     net::TcpStream,
     sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
 };
-use tracing::{error, info};
+use tracing::{debug, error, info_span, Instrument};
 
 pub struct NetworkManager {
-    _incoming_msg_tx: UnboundedSender<String>,
-    incoming_msg_rx: UnboundedReceiver<String>,
-    sending_msg_tx: UnboundedSender<String>,
+    _incoming_msg_tx: UnboundedSender<ServerMessage>,
+    incoming_msg_rx: UnboundedReceiver<ServerMessage>,
+    sending_msg_tx: UnboundedSender<ClientMessage>,
 }
 
 impl NetworkManager {
@@ -20,13 +21,16 @@ impl NetworkManager {
         let (sending_msg_tx, sending_msg_rx) = mpsc::unbounded_channel();
 
         let incoming_msg_tx_clone = incoming_msg_tx.clone();
-        tokio::spawn(async move {
-            if let Err(e) =
-                Self::read_and_write_stream(stream, incoming_msg_tx_clone, sending_msg_rx).await
-            {
-                error!("Failed to read and write stream: {}", e);
+        tokio::spawn(
+            async move {
+                if let Err(e) =
+                    Self::read_and_write_stream(stream, incoming_msg_tx_clone, sending_msg_rx).await
+                {
+                    error!("Failed to read and write stream: {}", e);
+                }
             }
-        });
+            .instrument(info_span!("connection", addr)),
+        );
 
         Ok(Self {
             _incoming_msg_tx: incoming_msg_tx.clone(),
@@ -37,8 +41,8 @@ impl NetworkManager {
 
     async fn read_and_write_stream(
         mut stream: TcpStream,
-        incoming_msg_tx: UnboundedSender<String>,
-        mut sending_msg_rx: UnboundedReceiver<String>,
+        incoming_msg_tx: UnboundedSender<ServerMessage>,
+        mut sending_msg_rx: UnboundedReceiver<ClientMessage>,
     ) -> Result<()> {
         let (reader, mut writer) = stream.split();
         let mut reader = BufReader::new(reader);
@@ -50,9 +54,14 @@ impl NetworkManager {
                     match result {
                         Ok(0) => {}
                         Ok(_) => {
-                            info!("Received message: {}", line);
-                            if let Err(e) = incoming_msg_tx.send(line.clone()){
-                                error!("Failed to send incoming message: {}", e);
+                            debug!(raw = line.trim_end(), "Received message");
+                            match protocol::decode(&line) {
+                                Ok(msg) => {
+                                    if let Err(e) = incoming_msg_tx.send(msg) {
+                                        error!("Failed to send incoming message: {}", e);
+                                    }
+                                }
+                                Err(e) => error!("Failed to decode incoming message: {}", e),
                             }
                             line.clear();
                         }
@@ -61,8 +70,8 @@ impl NetworkManager {
                 },
                 message = sending_msg_rx.recv() => {
                     if let Some(msg) = message {
-                        info!("Sending message: {}", msg);
-                        let msg = format!("{msg}\n");
+                        debug!(?msg, "Sending message");
+                        let msg = protocol::encode(&msg)?;
                         if let Err(e) = writer.write_all(msg.as_bytes()).await {
                             error!("Failed to send message: {}", e);
                         }
@@ -71,14 +80,14 @@ impl NetworkManager {
             }
         }
     }
-    pub fn send_message(&self, message: String) {
+    pub fn send_message(&self, message: ClientMessage) {
         let sender = self.sending_msg_tx.clone();
         tokio::spawn(async move {
             let _ = sender.send(message);
         });
     }
 
-    pub fn get_incoming_messages(&mut self) -> &mut UnboundedReceiver<String> {
+    pub fn get_incoming_messages(&mut self) -> &mut UnboundedReceiver<ServerMessage> {
         &mut self.incoming_msg_rx
     }
 }
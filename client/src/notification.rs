@@ -0,0 +1,40 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::Notification;
+
+/// Longest message text put in a desktop notification.
+const MAX_BODY_CHARS: usize = 200;
+
+/// Writes the escape sequence for `notification` straight to the terminal.
+pub fn notify(notification: Notification, title: &str, body: &str) -> Result<()> {
+    let sequence = match notification {
+        Notification::Off => return Ok(()),
+        Notification::Bell => "\x07".to_string(),
+        Notification::Osc9 => format!("\x1b]9;{}: {}\x07", sanitize(title), sanitize(body)),
+        // The title can't contain the separator, the body is the rest of the sequence
+        Notification::Osc777 => format!(
+            "\x1b]777;notify;{};{}\x07",
+            sanitize(title).replace(';', ","),
+            sanitize(body)
+        ),
+    };
+    let mut stdout = std::io::stdout();
+    stdout.write_all(sequence.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Keeps `text` on one line, without control characters that would end the sequence early.
+fn sanitize(text: &str) -> String {
+    let mut sanitized: String = text
+        .chars()
+        .take(MAX_BODY_CHARS)
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    if text.chars().count() > MAX_BODY_CHARS {
+        sanitized.push('…');
+    }
+    sanitized
+}
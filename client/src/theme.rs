@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use tracing::Level;
+
+use crate::config_dir;
+
+/// Every style used to draw the UI.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    /// Active tab, focused input and popup borders.
+    pub accent: Style,
+    pub key_hints: Style,
+    pub fps: Style,
+    pub mode: Style,
+    /// Secondary text: timestamps, hints, markers like "(edited)".
+    pub dim: Style,
+    /// The selected line in lists.
+    pub selected: Style,
+    pub search_prompt: Style,
+    pub search_match: Style,
+    /// The "new messages" divider.
+    pub divider: Style,
+    pub direct_message: Style,
+    /// Links in messages.
+    pub link: Style,
+    /// `code` and code blocks in messages.
+    pub code: Style,
+    /// `> ` quotes in messages.
+    pub quote: Style,
+    /// Messages that mention you or one of your highlight words.
+    pub mention: Style,
+    /// Server notices like people joining and leaving.
+    pub notice: Style,
+    /// Your own name on your messages, in place of its nickname color.
+    pub own_message: Style,
+    /// Colors other people's names are picked from, so each name always gets the same one.
+    pub nick_colors: Vec<Color>,
+    pub own_reaction: Style,
+    pub log_fields: Style,
+    pub log_error: Style,
+    pub log_warn: Style,
+    pub log_info: Style,
+    pub log_debug: Style,
+    pub log_trace: Style,
+}
+
+/// Names of the built-in themes.
+pub const THEME_NAMES: &[&str] = &["dark", "light", "high-contrast", "no-color"];
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            accent: Style::default().fg(Color::Green),
+            key_hints: Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+            fps: Style::default().fg(Color::Blue),
+            mode: Style::default().add_modifier(Modifier::BOLD),
+            dim: Style::default().fg(Color::DarkGray),
+            selected: Style::default().bg(Color::DarkGray),
+            search_prompt: Style::default().fg(Color::Yellow),
+            search_match: Style::default().fg(Color::Black).bg(Color::Yellow),
+            divider: Style::default().fg(Color::Red),
+            direct_message: Style::default().fg(Color::Magenta),
+            link: Style::default()
+                .fg(Color::LightBlue)
+                .add_modifier(Modifier::UNDERLINED),
+            code: Style::default().fg(Color::LightYellow),
+            quote: Style::default().fg(Color::Gray),
+            mention: Style::default().bg(Color::Indexed(52)),
+            notice: Style::default()
+                .fg(Color::Gray)
+                .add_modifier(Modifier::ITALIC),
+            own_message: Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+            nick_colors: vec![
+                Color::Red,
+                Color::Yellow,
+                Color::Blue,
+                Color::Magenta,
+                Color::Cyan,
+                Color::LightRed,
+                Color::LightGreen,
+                Color::LightYellow,
+                Color::LightBlue,
+                Color::LightMagenta,
+                Color::LightCyan,
+            ],
+            own_reaction: Style::default().fg(Color::Yellow),
+            log_fields: Style::default().fg(Color::Cyan),
+            log_error: Style::default().fg(Color::Red),
+            log_warn: Style::default().fg(Color::Yellow),
+            log_info: Style::default().fg(Color::Green),
+            log_debug: Style::default().fg(Color::Blue),
+            log_trace: Style::default().fg(Color::Magenta),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            accent: Style::default().fg(Color::Blue),
+            key_hints: Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            fps: Style::default().fg(Color::Magenta),
+            dim: Style::default().fg(Color::DarkGray),
+            selected: Style::default().bg(Color::Gray),
+            search_prompt: Style::default().fg(Color::Magenta),
+            search_match: Style::default().fg(Color::Black).bg(Color::LightYellow),
+            link: Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::UNDERLINED),
+            code: Style::default().fg(Color::Indexed(124)),
+            quote: Style::default().fg(Color::DarkGray),
+            mention: Style::default().bg(Color::Indexed(224)),
+            notice: Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC),
+            own_message: Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            nick_colors: vec![
+                Color::Red,
+                Color::Green,
+                Color::Magenta,
+                Color::Cyan,
+                Color::Indexed(130),
+                Color::Indexed(25),
+                Color::Indexed(90),
+                Color::Indexed(28),
+            ],
+            own_reaction: Style::default().fg(Color::Blue),
+            log_fields: Style::default().fg(Color::Blue),
+            log_warn: Style::default().fg(Color::Magenta),
+            log_trace: Style::default().fg(Color::DarkGray),
+            ..Self::dark()
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            accent: Style::default()
+                .fg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+            key_hints: Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            fps: Style::default().fg(Color::White),
+            dim: Style::default().fg(Color::Gray),
+            selected: Style::default().fg(Color::Black).bg(Color::White),
+            search_prompt: Style::default().fg(Color::LightYellow),
+            search_match: Style::default().fg(Color::Black).bg(Color::LightYellow),
+            divider: Style::default().fg(Color::LightRed),
+            direct_message: Style::default().fg(Color::LightMagenta),
+            link: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::UNDERLINED),
+            code: Style::default().fg(Color::LightYellow),
+            quote: Style::default().fg(Color::White),
+            mention: Style::default().bg(Color::Red).add_modifier(Modifier::BOLD),
+            notice: Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::ITALIC),
+            own_message: Style::default()
+                .fg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+            nick_colors: vec![
+                Color::LightRed,
+                Color::LightYellow,
+                Color::LightBlue,
+                Color::LightMagenta,
+                Color::LightCyan,
+                Color::White,
+            ],
+            own_reaction: Style::default().fg(Color::LightYellow),
+            log_fields: Style::default().fg(Color::LightCyan),
+            log_error: Style::default().fg(Color::LightRed),
+            log_warn: Style::default().fg(Color::LightYellow),
+            log_info: Style::default().fg(Color::LightGreen),
+            log_debug: Style::default().fg(Color::LightBlue),
+            log_trace: Style::default().fg(Color::LightMagenta),
+            ..Self::dark()
+        }
+    }
+
+    /// No colors at all, only text attributes, as asked for by `NO_COLOR`.
+    pub fn no_color() -> Self {
+        let plain = Style::default();
+        Self {
+            accent: plain.add_modifier(Modifier::BOLD),
+            key_hints: plain.add_modifier(Modifier::BOLD),
+            fps: plain,
+            mode: plain.add_modifier(Modifier::BOLD),
+            dim: plain,
+            selected: plain.add_modifier(Modifier::REVERSED),
+            search_prompt: plain,
+            search_match: plain.add_modifier(Modifier::REVERSED),
+            divider: plain,
+            direct_message: plain.add_modifier(Modifier::ITALIC),
+            link: plain.add_modifier(Modifier::UNDERLINED),
+            code: plain,
+            quote: plain.add_modifier(Modifier::ITALIC),
+            mention: plain.add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            notice: plain.add_modifier(Modifier::ITALIC),
+            own_message: plain.add_modifier(Modifier::BOLD),
+            nick_colors: Vec::new(),
+            own_reaction: plain.add_modifier(Modifier::BOLD),
+            log_fields: plain,
+            log_error: plain.add_modifier(Modifier::BOLD),
+            log_warn: plain.add_modifier(Modifier::BOLD),
+            log_info: plain,
+            log_debug: plain,
+            log_trace: plain,
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            "no-color" => Some(Self::no_color()),
+            _ => None,
+        }
+    }
+
+    /// Loads the theme called `name`: a built-in one, or `themes/<name>.toml` in the
+    /// config directory. A non-empty `NO_COLOR` environment variable always wins.
+    pub fn load(name: &str) -> Result<Self> {
+        if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return Ok(Self::no_color());
+        }
+        if let Some(theme) = Self::builtin(name) {
+            return Ok(theme);
+        }
+        let path = Self::path(name)
+            .ok_or_else(|| anyhow!("No config directory to load theme {name} from"))?;
+        let contents = std::fs::read_to_string(&path).with_context(|| {
+            format!(
+                "Unknown theme {name}: not one of {} and failed to read {}",
+                THEME_NAMES.join(", "),
+                path.display()
+            )
+        })?;
+        Self::parse(&contents).with_context(|| format!("Invalid theme {}", path.display()))
+    }
+
+    pub fn path(name: &str) -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("themes").join(format!("{name}.toml")))
+    }
+
+    /// Parses a user theme: a built-in `base` theme, `dark` by default, with some of its
+    /// styles replaced.
+    fn parse(contents: &str) -> Result<Self> {
+        let file: ThemeFile = toml::from_str(contents)?;
+        let base = file.base.as_deref().unwrap_or("dark");
+        let mut theme = Self::builtin(base).ok_or_else(|| anyhow!("Unknown base theme {base}"))?;
+        if let Some(colors) = file.nick_colors {
+            theme.nick_colors = colors
+                .iter()
+                .map(|color| parse_color(color))
+                .collect::<Result<_>>()
+                .context("Invalid nick_colors")?;
+        }
+        for (name, style) in file.styles {
+            let target = theme
+                .style_mut(&name)
+                .ok_or_else(|| anyhow!("Unknown style {name}"))?;
+            *target = style
+                .to_style()
+                .with_context(|| format!("Invalid style {name}"))?;
+        }
+        Ok(theme)
+    }
+
+    fn style_mut(&mut self, name: &str) -> Option<&mut Style> {
+        Some(match name {
+            "accent" => &mut self.accent,
+            "key_hints" => &mut self.key_hints,
+            "fps" => &mut self.fps,
+            "mode" => &mut self.mode,
+            "dim" => &mut self.dim,
+            "selected" => &mut self.selected,
+            "search_prompt" => &mut self.search_prompt,
+            "search_match" => &mut self.search_match,
+            "divider" => &mut self.divider,
+            "direct_message" => &mut self.direct_message,
+            "link" => &mut self.link,
+            "code" => &mut self.code,
+            "quote" => &mut self.quote,
+            "mention" => &mut self.mention,
+            "notice" => &mut self.notice,
+            "own_message" => &mut self.own_message,
+            "own_reaction" => &mut self.own_reaction,
+            "log_fields" => &mut self.log_fields,
+            "log_error" => &mut self.log_error,
+            "log_warn" => &mut self.log_warn,
+            "log_info" => &mut self.log_info,
+            "log_debug" => &mut self.log_debug,
+            "log_trace" => &mut self.log_trace,
+            _ => return None,
+        })
+    }
+
+    /// The style for `name`: always the same color from the palette, picked by a hash of it.
+    pub fn nick(&self, name: &str) -> Style {
+        if self.nick_colors.is_empty() {
+            return Style::default();
+        }
+        // FNV-1a, so the colors stay the same across runs and builds
+        let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        });
+        Style::default().fg(self.nick_colors[hash as usize % self.nick_colors.len()])
+    }
+
+    /// The style for log lines of `level`.
+    pub fn level(&self, level: Level) -> Style {
+        match level {
+            Level::ERROR => self.log_error,
+            Level::WARN => self.log_warn,
+            Level::INFO => self.log_info,
+            Level::DEBUG => self.log_debug,
+            Level::TRACE => self.log_trace,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+#[derive(Deserialize)]
+struct ThemeFile {
+    base: Option<String>,
+    nick_colors: Option<Vec<String>>,
+    #[serde(flatten)]
+    styles: HashMap<String, StyleDef>,
+}
+
+/// A style in a theme file, either just a foreground color like `"green"` or `"#00ff00"`,
+/// or a table like `{ fg = "black", bg = "yellow", bold = true }`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StyleDef {
+    Color(String),
+    Full {
+        fg: Option<String>,
+        bg: Option<String>,
+        #[serde(default)]
+        bold: bool,
+        #[serde(default)]
+        italic: bool,
+        #[serde(default)]
+        underlined: bool,
+        #[serde(default)]
+        reversed: bool,
+    },
+}
+
+fn parse_color(color: &str) -> Result<Color> {
+    Color::from_str(color).map_err(|_| anyhow!("Unknown color {color}"))
+}
+
+impl StyleDef {
+    fn to_style(&self) -> Result<Style> {
+        Ok(match self {
+            StyleDef::Color(fg) => Style::default().fg(parse_color(fg)?),
+            StyleDef::Full {
+                fg,
+                bg,
+                bold,
+                italic,
+                underlined,
+                reversed,
+            } => {
+                let mut style = Style::default();
+                if let Some(fg) = fg {
+                    style = style.fg(parse_color(fg)?);
+                }
+                if let Some(bg) = bg {
+                    style = style.bg(parse_color(bg)?);
+                }
+                for (enabled, modifier) in [
+                    (bold, Modifier::BOLD),
+                    (italic, Modifier::ITALIC),
+                    (underlined, Modifier::UNDERLINED),
+                    (reversed, Modifier::REVERSED),
+                ] {
+                    if *enabled {
+                        style = style.add_modifier(modifier);
+                    }
+                }
+                style
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_builtin_theme_loads_by_name() {
+        for name in THEME_NAMES {
+            assert!(Theme::builtin(name).is_some(), "{name}");
+        }
+        assert!(Theme::builtin("solarized").is_none());
+    }
+
+    #[test]
+    fn parses_colors_and_full_styles() {
+        let theme = Theme::parse(
+            r##"
+            base = "light"
+            accent = "#ff0000"
+            mention = { fg = "black", bg = "yellow", bold = true }
+            nick_colors = ["red", "blue"]
+            "##,
+        )
+        .unwrap();
+        assert_eq!(theme.accent, Style::default().fg(Color::Rgb(255, 0, 0)));
+        assert_eq!(
+            theme.mention,
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(theme.nick_colors, [Color::Red, Color::Blue]);
+        // Everything else comes from the base
+        assert_eq!(theme.link, Theme::light().link);
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert!(Theme::parse("base = \"nope\"").is_err());
+        assert!(Theme::parse("acent = \"red\"").is_err());
+        assert!(Theme::parse("accent = \"reddish\"").is_err());
+        assert!(Theme::parse("nick_colors = [\"red\", \"nope\"]").is_err());
+    }
+
+    #[test]
+    fn nick_colors_are_stable() {
+        let theme = Theme::dark();
+        assert_eq!(theme.nick("alice"), theme.nick("alice"));
+        let colors: std::collections::HashSet<_> = ["alice", "bob", "carol", "dave", "erin"]
+            .iter()
+            .map(|name| theme.nick(name).fg)
+            .collect();
+        assert!(colors.len() > 1);
+        assert_eq!(Theme::no_color().nick("alice"), Style::default());
+    }
+}
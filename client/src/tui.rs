@@ -1,5 +1,9 @@
 use anyhow::Result;
-use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::event::{
+    DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+    EnableFocusChange, EnableMouseCapture, Event, KeyboardEnhancementFlags, MouseEvent,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
 use crossterm::{
     event::{KeyEvent, KeyEventKind},
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
@@ -7,13 +11,15 @@ use crossterm::{
 use futures::{FutureExt, StreamExt};
 use ratatui::backend::CrosstermBackend;
 
-use ratatui::widgets::ListItem;
+use protocol::{ClientMessage, ServerMessage};
 use tokio::{
     sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
     task::JoinHandle,
 };
 use tracing::error;
 
+use crate::LogEntry;
+
 #[derive(Clone, Debug)]
 pub enum Message {
     Quit,
@@ -21,9 +27,15 @@ pub enum Message {
     Tick,
     Render,
     Key(KeyEvent),
-    ReceivedNetworkMessage(String),
-    SendNetworkMessage(String),
-    Log(ListItem<'static>),
+    Resize(u16, u16),
+    Mouse(MouseEvent),
+    /// Text pasted into the terminal, as a single event thanks to bracketed paste.
+    Paste(String),
+    FocusGained,
+    FocusLost,
+    ReceivedNetworkMessage(ServerMessage),
+    SendNetworkMessage(ClientMessage),
+    Log(LogEntry),
     RegisterUser(String),
 }
 
@@ -34,6 +46,8 @@ pub struct Tui {
     pub event_tx: UnboundedSender<Message>,
     pub frame_rate: f64,
     pub tick_rate: f64,
+    /// Whether the terminal reports modifiers on keys like Enter, e.g. for Shift+Enter.
+    pub keyboard_enhanced: bool,
 }
 
 impl Tui {
@@ -48,12 +62,26 @@ impl Tui {
             event_tx,
             frame_rate,
             tick_rate,
+            keyboard_enhanced: false,
         })
     }
 
     pub fn enter(&mut self) -> Result<()> {
         crossterm::terminal::enable_raw_mode()?;
-        crossterm::execute!(std::io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+        crossterm::execute!(
+            std::io::stdout(),
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableBracketedPaste,
+            EnableFocusChange
+        )?;
+        self.keyboard_enhanced = crossterm::terminal::supports_keyboard_enhancement()?;
+        if self.keyboard_enhanced {
+            crossterm::execute!(
+                std::io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+            )?;
+        }
         self.start();
         Ok(())
     }
@@ -61,7 +89,16 @@ impl Tui {
     pub fn exit(&mut self) -> Result<()> {
         if crossterm::terminal::is_raw_mode_enabled()? {
             self.terminal.flush()?;
-            crossterm::execute!(std::io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+            if self.keyboard_enhanced {
+                crossterm::execute!(std::io::stdout(), PopKeyboardEnhancementFlags)?;
+            }
+            crossterm::execute!(
+                std::io::stdout(),
+                LeaveAlternateScreen,
+                DisableMouseCapture,
+                DisableBracketedPaste,
+                DisableFocusChange
+            )?;
             crossterm::terminal::disable_raw_mode()?;
             self.terminal.show_cursor()?;
         }
@@ -87,17 +124,18 @@ impl Tui {
                 tokio::select! {
                       maybe_event = crossterm_event => {
                         match maybe_event {
-                          Some(Ok(evt)) => {
-                            match evt {
-                              crossterm::event::Event::Key(key) => {
-                                if key.kind == KeyEventKind::Press {
-                                    if let Err(e) = event_tx.send(Message::Key(key)) {
-                                        error!("Failed to send key event: {}", e);
-                                    }
-                                }
-                              },
-                              _ =>{}
-
+                          Some(Ok(event)) => {
+                            let message = match event {
+                                Event::Key(key) if key.kind == KeyEventKind::Press => Message::Key(key),
+                                Event::Key(_) => continue,
+                                Event::Resize(width, height) => Message::Resize(width, height),
+                                Event::Mouse(mouse) => Message::Mouse(mouse),
+                                Event::Paste(text) => Message::Paste(text),
+                                Event::FocusGained => Message::FocusGained,
+                                Event::FocusLost => Message::FocusLost,
+                            };
+                            if let Err(e) = event_tx.send(message) {
+                                error!("Failed to send terminal event: {}", e);
                             }
                           }
                           Some(Err(_e)) => {
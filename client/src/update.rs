@@ -1,71 +1,731 @@
 use crossterm::event::{
-    Event,
-    KeyCode::{self, Char},
+    Event, KeyCode::Char, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
 };
-use tracing::error;
-use tui_input::backend::crossterm::EventHandler;
+use protocol::{ChatMessage, ClientMessage, ServerMessage, LOBBY};
+use ratatui::layout::Position;
+use tracing::{error, info};
+use tui_input::{backend::crossterm::EventHandler, Input, InputRequest};
 
-use crate::{model::model::ActiveTab, InputMode, Message, Model};
+use crate::{
+    dump_logs,
+    links::{copy_to_clipboard, open_url},
+    model::input_history::ReverseSearch,
+    model::model::ActiveTab,
+    model::vi::ViOutcome,
+    next_log_level, notify, Action, ChatEntry, Command, Completion, HistorySearch, InputMode,
+    LinkPicker, Message, Model, Scope,
+};
 
 pub fn update(model: &mut Model, message: Message) {
+    let (selected, thread) = (model.selected, model.thread);
+    handle_message(model, message);
+    // The chat follows the selection again once it moves, until it's scrolled with the mouse
+    if model.selected != selected || model.thread != thread {
+        model.chat_scroll = None;
+    }
+}
+
+fn handle_message(model: &mut Model, message: Message) {
+    // Ticks only change what's shown when something times out, and most mouse events are
+    // movement we ignore, both checked below
+    if !matches!(message, Message::Tick | Message::Mouse(_)) {
+        model.dirty = true;
+    }
+    let chat_focused = model.is_user_registered && model.active_tab == ActiveTab::Chat;
+    // A paste changes the input under the completion, which would put back or complete
+    // stale text. Clicks clear it in `click`.
+    if let Message::Paste(_) = &message {
+        model.completion = None;
+    }
+    if let Message::Key(key) = &message {
+        // Keys outside editing mode, like vi edits, always end the completion
+        let action = model
+            .keymap
+            .action(InputMode::Editing, *key)
+            .filter(|_| model.input_mode == InputMode::Editing);
+        if !matches!(action, Some(Action::Complete | Action::CompleteBack)) {
+            // Stop editing puts back what was typed, any other key keeps the completion and goes on
+            if let Some(completion) = model.completion.take() {
+                if action == Some(Action::StopEditing) {
+                    model.input = completion.original();
+                    return;
+                }
+            }
+        }
+    }
     match message {
+        Message::Key(key) if model.history_search.is_some() => history_search_key(model, key),
+        Message::Key(key) if model.link_picker.is_some() => link_picker_key(model, key),
+        Message::Key(key) if model.local_search.editing => local_search_key(model, key),
+        Message::Key(key) if model.input_history.search.is_some() => reverse_search_key(model, key),
+        Message::Key(key) if model.vi.pending.is_some() => vi_key(model, key),
         Message::Key(key) => match model.input_mode {
-            InputMode::Normal => match key.code {
-                Char('q') => {
+            InputMode::Normal => match model.keymap.action(InputMode::Normal, key) {
+                Some(Action::Quit) => {
                     if let Err(e) = model.message_tx.send(Message::Quit) {
                         error!("Failed to send quit message: {}", e)
                     }
                 }
-                KeyCode::Enter => {
-                    if model.active_tab == ActiveTab::Chat || !model.is_user_registered {
+                Some(Action::StartEditing) if model.active_tab == ActiveTab::Mentions => {
+                    let message = model
+                        .mention_selected
+                        .and_then(|id| model.find_message(id))
+                        .cloned();
+                    if let Some(message) = message {
+                        model.jump_to(message);
+                    }
+                }
+                Some(Action::StartEditing)
+                    if model.active_tab == ActiveTab::Chat || !model.is_user_registered =>
+                {
+                    model.input_mode = InputMode::Editing;
+                }
+                Some(Action::SwitchTab) => switch_tab(model, model.active_tab.next()),
+                Some(Action::Search)
+                    if model.is_user_registered && model.active_tab != ActiveTab::Mentions =>
+                {
+                    model.local_search.clear();
+                    model.local_search.editing = true;
+                }
+                Some(Action::NextMatch) if model.local_search.is_active() => {
+                    model.jump_to_match(true)
+                }
+                Some(Action::PreviousMatch) if model.local_search.is_active() => {
+                    model.jump_to_match(false)
+                }
+                Some(Action::ToggleFilter) if model.local_search.is_active() => {
+                    model.local_search.filter = !model.local_search.filter;
+                }
+                Some(Action::Cancel) if model.local_search.is_active() => {
+                    model.local_search.clear();
+                    model.log_selected = None;
+                }
+                Some(Action::CycleLogLevel) if model.active_tab == ActiveTab::Logs => {
+                    model.log_level = next_log_level(model.log_level);
+                    model.log_selected = None;
+                }
+                Some(Action::DumpLogs) if model.active_tab == ActiveTab::Logs => {
+                    match dump_logs(model.logs.iter()) {
+                        Ok(path) => info!("Dumped the log buffer to {}", path.display()),
+                        Err(e) => error!("Failed to dump the log buffer: {}", e),
+                    }
+                }
+                Some(Action::SelectNext) if chat_focused => model.move_selection(1),
+                Some(Action::SelectPrevious) if chat_focused => model.move_selection(-1),
+                Some(Action::SelectNext) if model.active_tab == ActiveTab::Mentions => {
+                    model.move_mention_selection(1)
+                }
+                Some(Action::SelectPrevious) if model.active_tab == ActiveTab::Mentions => {
+                    model.move_mention_selection(-1)
+                }
+                Some(Action::Reply) if chat_focused && model.selected.is_some() => {
+                    model.replying_to = model.selected;
+                    model.input_mode = InputMode::Editing;
+                }
+                Some(Action::EditMessage) if chat_focused => {
+                    // The selected message if it's ours, our newest one otherwise
+                    let message = model
+                        .selected
+                        .and_then(|id| model.find_message(id))
+                        .filter(|message| {
+                            message.author == model.username
+                                && !message.deleted
+                                && message.to.is_none()
+                        })
+                        .or_else(|| model.last_own_message());
+                    if let Some(message) = message {
+                        let (id, body) = (message.id, message.body.clone());
+                        model.editing = Some(id);
+                        model.input = Input::new(body);
                         model.input_mode = InputMode::Editing;
                     }
                 }
-                KeyCode::Tab => {
-                    model.active_tab = match model.active_tab {
-                        ActiveTab::Chat => ActiveTab::Logs,
-                        ActiveTab::Logs => ActiveTab::Chat,
+                Some(Action::React) if chat_focused && model.selected.is_some() => {
+                    model.input = Input::new("/react ".to_string());
+                    model.input_mode = InputMode::Editing;
+                }
+                Some(Action::ToggleThread) if chat_focused => {
+                    model.thread = match model.thread {
+                        Some(_) => None,
+                        None => model.selected,
+                    };
+                }
+                Some(Action::PickLink) if model.is_user_registered => {
+                    let links = model.recent_links();
+                    if links.is_empty() {
+                        model.push_notice("No links in the chat");
+                    } else {
+                        model.link_picker = Some(LinkPicker::new(links));
+                    }
+                }
+                Some(Action::ToggleRaw) if chat_focused => {
+                    if let Some(id) = model.selected {
+                        if !model.raw_messages.remove(&id) {
+                            model.raw_messages.insert(id);
+                        }
                     }
                 }
+                // Only left over from editing in vi mode
+                Some(Action::Cancel)
+                    if chat_focused && (model.editing.is_some() || model.replying_to.is_some()) =>
+                {
+                    if model.editing.take().is_some() {
+                        model.input.reset();
+                    }
+                    model.replying_to = None;
+                }
+                Some(Action::Cancel) if chat_focused => {
+                    if model.thread.is_some() {
+                        model.thread = None;
+                    } else {
+                        model.selected = None;
+                    }
+                }
+                _ if chat_focused && model.config.vi_mode => vi_key(model, key),
                 _ => {}
             },
-            InputMode::Editing => match key.code {
-                KeyCode::Enter => {
+            InputMode::Editing => match model.keymap.action(InputMode::Editing, key) {
+                Some(Action::Newline) if model.is_user_registered => {
+                    model.input.handle(InputRequest::InsertChar('\n'));
+                    input_changed(model);
+                }
+                Some(Action::Send) => {
                     if model.is_user_registered {
-                        let msg = model.input.value().to_string();
-                        if let Err(e) = model.message_tx.send(Message::SendNetworkMessage(msg)) {
-                            error!("Failed to send message: {}", e)
-                        }
+                        submit_input(model);
                         model.input.reset();
+                        model.unread_divider = None;
+                        stop_typing(model);
                     } else {
-                        let username = format!("username:{}", model.input.value().to_string());
+                        let username = model.input.value().to_string();
                         if let Err(e) = model.message_tx.send(Message::RegisterUser(username)) {
                             error!("Failed to send register message: {}", e)
                         }
                     }
                 }
-                KeyCode::Esc => {
+                Some(Action::HistoryOlder)
+                    if model.is_user_registered && model.editing.is_none() =>
+                {
+                    if let Some(entry) = model.input_history.older(model.input.value()) {
+                        model.input = Input::new(entry.to_string());
+                    }
+                }
+                Some(Action::HistoryNewer)
+                    if model.is_user_registered && model.editing.is_none() =>
+                {
+                    if let Some(entry) = model.input_history.newer() {
+                        model.input = Input::new(entry);
+                    }
+                }
+                Some(Action::SearchHistory)
+                    if model.is_user_registered && model.editing.is_none() =>
+                {
+                    model.input_history.search = Some(ReverseSearch {
+                        query: Input::default(),
+                        found: None,
+                        draft: model.input.value().to_string(),
+                    });
+                }
+                Some(Action::Complete) if model.is_user_registered => complete(model, 1),
+                Some(Action::CompleteBack) if model.is_user_registered => complete(model, -1),
+                Some(Action::StopEditing) if model.config.vi_mode && model.is_user_registered => {
+                    // Keep the draft, edit or reply for normal mode, where cancel drops them
+                    model.vi.clamp(&mut model.input);
+                    model.input_mode = InputMode::Normal;
+                    stop_typing(model);
+                }
+                Some(Action::StopEditing) => {
+                    if model.editing.take().is_some() {
+                        model.input.reset();
+                    }
+                    model.replying_to = None;
                     model.input_mode = InputMode::Normal;
+                    stop_typing(model);
                 }
                 _ => {
-                    model.input.handle_event(&Event::Key(key));
+                    let changed = model
+                        .input
+                        .handle_event(&Event::Key(key))
+                        .is_some_and(|state| state.value);
+                    if changed && model.is_user_registered {
+                        model.input_history.reset_position();
+                        input_changed(model);
+                    }
                 }
             },
         },
         Message::RegisterUser(username) => {
-            model.network_manager.send_message(username);
-            model.is_user_registered = true;
-            model.input.reset();
-        }
-        Message::ReceivedNetworkMessage(msg) => {
-            model.messages.push(msg);
+            model.username = username.trim().to_string();
+            model
+                .network_manager
+                .send_message(ClientMessage::Register { username });
+            model.register_error = None;
         }
+        Message::ReceivedNetworkMessage(msg) => match msg {
+            ServerMessage::Registered => {
+                model.is_user_registered = true;
+                model.input.reset();
+            }
+            // Back to the name as typed, to pick another one
+            ServerMessage::Rejected { reason } => model.register_error = Some(reason),
+            ServerMessage::Chat(message) => {
+                model.typing.set_user_typing(message.author.clone(), false);
+                // Only mentions the user hasn't read yet
+                let new_mention = model.find_message(message.id).is_none()
+                    && message.id > model.last_read()
+                    && model.is_mention(&message);
+                if new_mention {
+                    mentioned(model, &message);
+                }
+                if model.active_tab == ActiveTab::Chat || message.author == model.username {
+                    model.mark_read(message.id);
+                }
+                match model.find_message_mut(message.id) {
+                    Some(existing) => *existing = message,
+                    None => model.messages.push(ChatEntry::Message(message)),
+                }
+            }
+            ServerMessage::Notice(notice) => model.messages.push(ChatEntry::Notice(notice)),
+            ServerMessage::Edited {
+                id,
+                body,
+                edited_at,
+            } => {
+                if let Some(message) = model.find_message_mut(id) {
+                    message.body = body;
+                    message.edited_at = Some(edited_at);
+                }
+            }
+            ServerMessage::Deleted { id } => {
+                if let Some(message) = model.find_message_mut(id) {
+                    message.body.clear();
+                    message.reactions.clear();
+                    message.deleted = true;
+                }
+                if model.editing == Some(id) {
+                    model.editing = None;
+                    model.input.reset();
+                }
+            }
+            ServerMessage::Reactions { id, reactions } => {
+                if let Some(message) = model.find_message_mut(id) {
+                    message.reactions = reactions;
+                }
+            }
+            ServerMessage::Typing { user, active } => model.typing.set_user_typing(user, active),
+            ServerMessage::ReadMarkers { markers } if model.config.sync_read_markers => {
+                for (conversation, id) in markers {
+                    let marker = model.read_markers.entry(conversation).or_default();
+                    *marker = id.max(*marker);
+                }
+                model.unread_divider = model.read_markers.get(LOBBY).copied();
+            }
+            ServerMessage::ReadMarkers { .. } => {}
+            ServerMessage::HistoryEnd => model.history_loaded = true,
+            ServerMessage::SearchResults { results } => {
+                if results.is_empty() {
+                    model.push_notice("No messages found");
+                } else {
+                    model.history_search = Some(HistorySearch::new(results));
+                }
+            }
+        },
         Message::SendNetworkMessage(msg) => {
             model.network_manager.send_message(msg);
         }
+        Message::Tick => {
+            if model.fps_counter.refresh() {
+                model.dirty = true;
+            }
+            if model.typing.expire() {
+                model.dirty = true;
+            }
+            if model.typing.is_idle() {
+                stop_typing(model);
+            }
+            if model.read_markers_changed && model.config.sync_read_markers {
+                model.read_markers_changed = false;
+                let msg = ClientMessage::MarkRead {
+                    conversation: LOBBY.to_string(),
+                    id: model.last_read(),
+                };
+                if let Err(e) = model.message_tx.send(Message::SendNetworkMessage(msg)) {
+                    error!("Failed to send read marker: {}", e)
+                }
+            }
+        }
         Message::Log(msg) => {
-            model.logs.push(msg);
+            model.push_log(msg);
+        }
+        Message::Paste(text) if model.local_search.editing => {
+            paste(&mut model.local_search.input, &text, false);
+        }
+        Message::Paste(text) if model.input_mode == InputMode::Editing => {
+            let pasted = paste(&mut model.input, &text, model.is_user_registered);
+            if pasted && model.is_user_registered {
+                input_changed(model);
+            }
+        }
+        Message::Mouse(mouse) => mouse_event(model, mouse),
+        Message::FocusGained => model.focused = true,
+        Message::FocusLost => model.focused = false,
+        _ => {}
+    }
+}
+
+fn mouse_event(model: &mut Model, mouse: MouseEvent) {
+    let offset = match mouse.kind {
+        MouseEventKind::ScrollUp => -1,
+        MouseEventKind::ScrollDown => 1,
+        MouseEventKind::Down(MouseButton::Left) => {
+            model.dirty = true;
+            click(model, Position::new(mouse.column, mouse.row));
+            return;
+        }
+        _ => return,
+    };
+    model.dirty = true;
+    if let Some(search) = model.history_search.as_mut() {
+        search.move_selection(offset);
+    } else if let Some(picker) = model.link_picker.as_mut() {
+        picker.move_selection(offset);
+    } else if model.is_user_registered {
+        match model.active_tab {
+            ActiveTab::Chat => model.scroll_chat(offset),
+            ActiveTab::Mentions => model.move_mention_selection(offset),
+            ActiveTab::Logs => model.scroll_logs(offset),
+        }
+    }
+}
+
+fn switch_tab(model: &mut Model, tab: ActiveTab) {
+    model.active_tab = tab;
+    match tab {
+        ActiveTab::Chat => model.open_chat(),
+        ActiveTab::Mentions => model.unread_mentions = 0,
+        ActiveTab::Logs => {}
+    }
+}
+
+/// Counts a new mention and alerts the user if they aren't looking at the terminal. Mentions
+/// in the history sent on joining are only counted, the user wasn't around for them.
+fn mentioned(model: &mut Model, message: &ChatMessage) {
+    if model.active_tab != ActiveTab::Mentions {
+        model.unread_mentions += 1;
+    }
+    if !model.focused && model.history_loaded {
+        let title = format!("{} mentioned you", message.author);
+        if let Err(e) = notify(model.config.notification, &title, &message.body) {
+            error!("Failed to send a notification: {}", e);
         }
+    }
+}
+
+/// Switches tabs, starts editing at the clicked character, or starts a direct message to
+/// the clicked user, depending on what is at `position`.
+fn click(model: &mut Model, position: Position) {
+    if model.history_search.is_some() || model.link_picker.is_some() || model.local_search.editing {
+        return;
+    }
+    model.completion = None;
+    if let Some(tab) = model.click_areas.tab_at(position) {
+        switch_tab(model, tab);
+    } else if let Some((line, column)) = model.click_areas.input_cursor_at(position) {
+        let cursor = cursor_index(model.input.value(), line, column);
+        model.input = std::mem::take(&mut model.input).with_cursor(cursor);
+        model.input_mode = InputMode::Editing;
+    } else if let Some(username) = model.click_areas.username_at(position) {
+        // Don't throw away a draft
+        if username != model.username && model.input.value().is_empty() {
+            model.input = Input::new(format!("/msg {username} "));
+            model.input_mode = InputMode::Editing;
+        }
+    }
+}
+
+/// The character offset of `column` on `line` of a multi-line input value.
+fn cursor_index(value: &str, line: usize, column: usize) -> usize {
+    let mut index = 0;
+    for (idx, text) in value.split('\n').enumerate() {
+        let len = text.chars().count();
+        if idx == line {
+            return index + column.min(len);
+        }
+        index += len + 1;
+    }
+    index.saturating_sub(1)
+}
+
+/// Inserts pasted text at the cursor as a single edit, returning whether anything was inserted.
+/// Line breaks become spaces unless the input is `multiline`.
+fn paste(input: &mut Input, text: &str, multiline: bool) -> bool {
+    let line_break = if multiline { '\n' } else { ' ' };
+    let text: String = text
+        .trim_end_matches(['\r', '\n'])
+        .replace("\r\n", "\n")
+        .chars()
+        .map(|c| {
+            if c == '\r' || c == '\n' {
+                line_break
+            } else {
+                c
+            }
+        })
+        .collect();
+    if text.is_empty() {
+        return false;
+    }
+    let cursor = input.cursor();
+    let mut value: String = input.value().chars().take(cursor).collect();
+    value.push_str(&text);
+    value.extend(input.value().chars().skip(cursor));
+    *input = Input::new(value).with_cursor(cursor + text.chars().count());
+    true
+}
+
+/// Edits the input with vi keys in normal mode. Any key other than a plain character
+/// cancels a pending operator.
+fn vi_key(model: &mut Model, key: KeyEvent) {
+    let Char(c) = key.code else {
+        model.vi.pending = None;
+        return;
+    };
+    if key
+        .modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        model.vi.pending = None;
+        return;
+    }
+    let value = model.input.value().to_string();
+    if model.vi.key(&mut model.input, c) == ViOutcome::StartEditing {
+        model.input_mode = InputMode::Editing;
+    }
+    if model.input.value() != value {
+        input_changed(model);
+    }
+}
+
+/// Completes the word before the cursor, or moves on to the next (positive `offset`) or
+/// previous candidate while completing.
+fn complete(model: &mut Model, offset: isize) {
+    match model.completion.as_mut() {
+        Some(completion) => completion.cycle(offset),
+        None => {
+            let Some(mut completion) = Completion::start(&model.input, &model.known_users()) else {
+                return;
+            };
+            if offset < 0 {
+                completion.cycle(offset);
+            }
+            model.completion = Some(completion);
+        }
+    }
+    let completion = model.completion.as_ref().unwrap();
+    model.input = completion.apply();
+    // Nothing to cycle through
+    if completion.candidates.len() == 1 {
+        model.completion = None;
+    }
+    input_changed(model);
+}
+
+fn reverse_search_key(model: &mut Model, key: KeyEvent) {
+    let Some(search) = model.input_history.search.as_mut() else {
+        return;
+    };
+    match model.keymap.action(Scope::ReverseSearch, key) {
+        // Accept the match for editing rather than sending it straight away
+        Some(Action::AcceptMatch) => model.input_history.search = None,
+        Some(Action::CancelReverseSearch) => cancel_reverse_search(model),
+        Some(Action::OlderMatch) => {
+            model.input_history.search_older();
+            show_search_match(model);
+        }
+        _ => {
+            let changed = search
+                .query
+                .handle_event(&Event::Key(key))
+                .is_some_and(|state| state.value);
+            if changed {
+                model.input_history.search_changed();
+                show_search_match(model);
+            }
+        }
+    }
+}
+
+/// Closes the reverse search, bringing back the input from before it.
+fn cancel_reverse_search(model: &mut Model) {
+    if let Some(search) = model.input_history.search.take() {
+        model.input = Input::new(search.draft);
+    }
+}
+
+fn show_search_match(model: &mut Model) {
+    if let Some(entry) = model.input_history.search_match() {
+        model.input = Input::new(entry.to_string());
+    }
+}
+
+fn local_search_key(model: &mut Model, key: KeyEvent) {
+    match model.keymap.action(Scope::Search, key) {
+        Some(Action::ConfirmSearch) => {
+            model.local_search.editing = false;
+            if model.local_search.is_active() {
+                model.jump_to_match(false);
+            }
+        }
+        Some(Action::CancelSearch) => model.local_search.clear(),
+        _ => {
+            model.local_search.input.handle_event(&Event::Key(key));
+        }
+    }
+}
+
+fn history_search_key(model: &mut Model, key: KeyEvent) {
+    let Some(search) = model.history_search.as_mut() else {
+        return;
+    };
+    match model.keymap.action(Scope::SearchResults, key) {
+        Some(Action::NextResult) => search.move_selection(1),
+        Some(Action::PreviousResult) => search.move_selection(-1),
+        Some(Action::JumpToResult) => {
+            if let Some(message) = search.selected().cloned() {
+                model.history_search = None;
+                model.jump_to(message);
+            }
+        }
+        Some(Action::CloseResults) => model.history_search = None,
         _ => {}
     }
 }
+
+fn link_picker_key(model: &mut Model, key: KeyEvent) {
+    let Some(picker) = model.link_picker.as_mut() else {
+        return;
+    };
+    match model.keymap.action(Scope::LinkPicker, key) {
+        Some(Action::NextLink) => picker.move_selection(1),
+        Some(Action::PreviousLink) => picker.move_selection(-1),
+        Some(Action::OpenLink) => {
+            if let Some(link) = picker.selected().map(str::to_string) {
+                model.link_picker = None;
+                if let Err(e) = open_url(&model.config.open_command, &link) {
+                    model.push_notice(format!("Failed to open {link}: {e:#}"));
+                }
+            }
+        }
+        Some(Action::CopyLink) => {
+            if let Some(link) = picker.selected().map(str::to_string) {
+                model.link_picker = None;
+                match copy_to_clipboard(&link) {
+                    Ok(()) => model.push_notice(format!("Copied {link}")),
+                    Err(e) => model.push_notice(format!("Failed to copy {link}: {e:#}")),
+                }
+            }
+        }
+        Some(Action::CloseLinks) => model.link_picker = None,
+        // The numbers shown next to the links pick them, unless bound to something else
+        _ => {
+            if let Char(c @ '1'..='9') = key.code {
+                picker.select_number(c as usize - '0' as usize);
+            }
+        }
+    }
+}
+
+/// Sends the contents of the input box as a chat message, an edit, or runs it as a command.
+fn submit_input(model: &mut Model) {
+    let text = model.input.value().to_string();
+    if model.editing.is_none() {
+        model.input_history.push(text.clone());
+    }
+    let msg = if let Some(id) = model.editing.take() {
+        if text.trim().is_empty() {
+            ClientMessage::Delete { id }
+        } else {
+            ClientMessage::Edit { id, body: text }
+        }
+    } else {
+        match Command::parse(&text) {
+            Some(Ok(command)) => {
+                run_command(model, command);
+                return;
+            }
+            Some(Err(e)) => {
+                model.push_notice(e.to_string());
+                return;
+            }
+            None => ClientMessage::Chat {
+                body: text,
+                reply_to: model.replying_to.take(),
+            },
+        }
+    };
+    if let Err(e) = model.message_tx.send(Message::SendNetworkMessage(msg)) {
+        error!("Failed to send message: {}", e)
+    }
+}
+
+fn run_command(model: &mut Model, command: Command) {
+    match command {
+        Command::Delete => match model.last_own_message() {
+            Some(message) => {
+                let msg = ClientMessage::Delete { id: message.id };
+                if let Err(e) = model.message_tx.send(Message::SendNetworkMessage(msg)) {
+                    error!("Failed to send message: {}", e)
+                }
+            }
+            None => model.push_notice("You have no messages to delete"),
+        },
+        Command::Direct { to, body } => {
+            let msg = ClientMessage::Direct { to, body };
+            if let Err(e) = model.message_tx.send(Message::SendNetworkMessage(msg)) {
+                error!("Failed to send message: {}", e)
+            }
+        }
+        Command::Search(query) => {
+            let msg = ClientMessage::Search(query);
+            if let Err(e) = model.message_tx.send(Message::SendNetworkMessage(msg)) {
+                error!("Failed to send message: {}", e)
+            }
+        }
+        Command::React(emoji) => {
+            let target = model
+                .selected
+                .or_else(|| model.last_message().map(|message| message.id));
+            match target {
+                Some(id) => {
+                    let msg = ClientMessage::React { id, emoji };
+                    if let Err(e) = model.message_tx.send(Message::SendNetworkMessage(msg)) {
+                        error!("Failed to send message: {}", e)
+                    }
+                }
+                None => model.push_notice("There is no message to react to"),
+            }
+        }
+    }
+}
+
+/// Lets the other users know we're typing, unless the input is empty or a command.
+fn input_changed(model: &mut Model) {
+    let value = model.input.value();
+    if value.is_empty() || value.starts_with('/') || model.editing.is_some() {
+        stop_typing(model);
+    } else if model.typing.input_changed() {
+        send_typing(model, true);
+    }
+}
+
+fn stop_typing(model: &mut Model) {
+    if model.typing.stop() {
+        send_typing(model, false);
+    }
+}
+
+fn send_typing(model: &Model, active: bool) {
+    let msg = ClientMessage::Typing { active };
+    if let Err(e) = model.message_tx.send(Message::SendNetworkMessage(msg)) {
+        error!("Failed to send typing message: {}", e)
+    }
+}
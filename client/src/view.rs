@@ -1,22 +1,38 @@
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Position, Rect};
-use ratatui::style::{Color, Modifier, Style, Stylize};
-use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Tabs};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span, StyledGrapheme};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs};
 use ratatui::Frame;
 
+use std::ops::Range;
+
+use chrono::Local;
+use protocol::{ChatMessage, MessageId};
+use unicode_width::UnicodeWidthStr;
+
+use crate::links::{find_link_areas, find_urls};
+use crate::markup::{markup, plain};
+use crate::model::local_search::find_matches;
 use crate::model::model::ActiveTab;
-use crate::{InputMode, Model};
+use crate::{Action, ChatEntry, ClickAreas, Completion, InputMode, Model};
 
-pub fn view(frame: &mut Frame<'_>, model: &Model) {
+/// Draws the UI, returning where its clickable parts ended up.
+pub fn view(frame: &mut Frame<'_>, model: &Model) -> ClickAreas {
+    let mut click_areas = ClickAreas::default();
     if model.is_user_registered {
-        render_app_view(frame, model, frame.area());
+        render_app_view(frame, model, frame.area(), &mut click_areas);
     } else {
-        render_register_view(frame, model, frame.area());
+        render_register_view(frame, model, frame.area(), &mut click_areas);
     }
+    click_areas
 }
 
-fn render_register_view(frame: &mut Frame<'_>, model: &Model, area: Rect) {
-
+fn render_register_view(
+    frame: &mut Frame<'_>,
+    model: &Model,
+    area: Rect,
+    click_areas: &mut ClickAreas,
+) {
     let register_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -29,65 +45,77 @@ fn render_register_view(frame: &mut Frame<'_>, model: &Model, area: Rect) {
     // Title
     let title = Paragraph::new("Enter your username")
         .alignment(Alignment::Center)
-        .style(
-            Style::default()
-                .fg(Color::Green)
-                .add_modifier(Modifier::BOLD),
-        );
+        .style(model.theme.accent.add_modifier(Modifier::BOLD));
     frame.render_widget(title, register_layout[0]);
 
     // User input with a border
     let input_area = register_layout[1];
-    let input_block =
-        Block::default()
-            .borders(Borders::ALL)
-            .style(if model.input_mode == InputMode::Editing {
-                Style::default().fg(Color::Green)
-            } else {
-                Style::default()
-            });
+    let error = model.register_error.as_deref().unwrap_or_default();
+    let input_block = Block::default()
+        .borders(Borders::ALL)
+        .title(Span::styled(error, model.theme.log_error))
+        .style(if model.input_mode == InputMode::Editing {
+            model.theme.accent
+        } else {
+            Style::default()
+        });
     frame.render_widget(input_block.clone(), input_area);
     let inner_input_area = input_block.inner(input_area);
+    click_areas.input = Some((inner_input_area, (0, 0)));
 
     // User input text
     let user_input = Paragraph::new(model.input.value()).style(match model.input_mode {
         InputMode::Normal => Style::default(),
-        InputMode::Editing => Style::default().fg(Color::Green),
+        InputMode::Editing => model.theme.accent,
     });
     frame.render_widget(user_input, inner_input_area);
 
     // Set cursor position if in editing mode
     if let InputMode::Editing = model.input_mode {
-        let cursor_pos = Position::new(inner_input_area.x + model.input.visual_cursor() as u16, inner_input_area.y);
+        let cursor_pos = Position::new(
+            inner_input_area.x + model.input.visual_cursor() as u16,
+            inner_input_area.y,
+        );
         frame.set_cursor_position(cursor_pos);
-
     }
 
     let keybindings = match model.input_mode {
-        InputMode::Normal => "q: quit | enter: edit",
-        InputMode::Editing => "q: quit | esc: stop editing",
+        InputMode::Normal => model
+            .keymap
+            .hints(&[(&[Action::Quit], "quit"), (&[Action::StartEditing], "edit")]),
+        InputMode::Editing => model.keymap.hints(&[
+            (&[Action::Send], "register"),
+            (&[Action::StopEditing], "stop editing"),
+        ]),
     };
     let keybindings_paragraph = Paragraph::new(keybindings)
         .alignment(Alignment::Left)
-        .style(
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        );
+        .style(model.theme.key_hints);
     frame.render_widget(keybindings_paragraph, register_layout[2]);
 }
 
-fn render_app_view(frame: &mut Frame<'_>, model: &Model, area: Rect) {
+fn render_app_view(frame: &mut Frame<'_>, model: &Model, area: Rect, click_areas: &mut ClickAreas) {
     // Tabs
-    let titles = vec!["Chat", "Logs"];
+    let unread = model.unread_count();
+    let chat_title = if unread > 0 && model.active_tab != ActiveTab::Chat {
+        format!("Chat ({unread})")
+    } else {
+        "Chat".to_string()
+    };
+    let mentions_title = if model.unread_mentions > 0 && model.active_tab != ActiveTab::Mentions {
+        format!("Mentions ({})", model.unread_mentions)
+    } else {
+        "Mentions".to_string()
+    };
+    let titles = vec![chat_title, mentions_title, "Logs".to_string()];
+    let title_widths: Vec<u16> = titles
+        .iter()
+        .map(|title| Span::raw(title.as_str()).width() as u16)
+        .collect();
 
     let tabs = Tabs::new(titles)
         .select(model.active_tab.get_idx())
-        .highlight_style(
-            Style::default()
-                .add_modifier(Modifier::BOLD)
-                .fg(Color::Green),
-        );
+        .highlight_style(model.theme.accent.add_modifier(Modifier::BOLD));
 
     let main_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -100,9 +128,21 @@ fn render_app_view(frame: &mut Frame<'_>, model: &Model, area: Rect) {
 
     // Render the tabs
     frame.render_widget(tabs, main_layout[0]);
+    // Each title is padded by a space on both sides and followed by a divider
+    let mut x = main_layout[0].x;
+    for (width, tab) in
+        title_widths
+            .into_iter()
+            .zip([ActiveTab::Chat, ActiveTab::Mentions, ActiveTab::Logs])
+    {
+        let title_area = Rect::new(x, main_layout[0].y, width + 2, 1).intersection(main_layout[0]);
+        click_areas.tabs.push((title_area, tab));
+        x = x.saturating_add(width + 3);
+    }
 
     match model.active_tab {
-        ActiveTab::Chat => render_chat_view(frame, model, main_layout[1]),
+        ActiveTab::Chat => render_chat_view(frame, model, main_layout[1], click_areas),
+        ActiveTab::Mentions => render_mentions_view(frame, model, main_layout[1]),
         ActiveTab::Logs => render_logs_view(frame, model, main_layout[1]),
     }
 
@@ -113,27 +153,88 @@ fn render_app_view(frame: &mut Frame<'_>, model: &Model, area: Rect) {
         .split(main_layout[2]);
 
     // Keybindings
-    let keybindings = match model.active_tab {
-        ActiveTab::Chat => match model.input_mode {
-            InputMode::Normal => "q: quit | enter: edit | tab: logs",
-            InputMode::Editing => "q: quit | esc: stop editing | tab: logs",
-        },
-        ActiveTab::Logs => "q: quit | tab: chat",
+    let keybindings = match (model.active_tab, model.input_mode) {
+        _ if model.input_history.search.is_some() => model.keymap.hints(&[
+            (&[Action::OlderMatch], "older"),
+            (&[Action::AcceptMatch], "accept"),
+            (&[Action::CancelReverseSearch], "cancel"),
+        ]),
+        (ActiveTab::Chat, InputMode::Normal) => model.keymap.hints(&[
+            (&[Action::Quit], "quit"),
+            (&[Action::StartEditing], "edit"),
+            (&[Action::SelectNext, Action::SelectPrevious], "select"),
+            (&[Action::EditMessage], "edit message"),
+            (&[Action::Reply], "reply"),
+            (&[Action::React], "react"),
+            (&[Action::ToggleThread], "thread"),
+            (&[Action::ToggleRaw], "raw"),
+            (&[Action::PickLink], "links"),
+            (&[Action::SwitchTab], "mentions"),
+        ]),
+        (ActiveTab::Chat, InputMode::Editing) => model.keymap.hints(&[
+            (&[Action::StopEditing], "stop editing"),
+            (&[Action::Newline], "new line"),
+            (&[Action::HistoryOlder, Action::HistoryNewer], "history"),
+            (&[Action::SearchHistory], "search history"),
+            (&[Action::Complete], "complete"),
+        ]),
+        (ActiveTab::Mentions, _) => model.keymap.hints(&[
+            (&[Action::Quit], "quit"),
+            (&[Action::SelectNext, Action::SelectPrevious], "select"),
+            (&[Action::StartEditing], "show in chat"),
+            (&[Action::SwitchTab], "logs"),
+        ]),
+        (ActiveTab::Logs, _) => model.keymap.hints(&[
+            (&[Action::Quit], "quit"),
+            (&[Action::CycleLogLevel], "level"),
+            (&[Action::DumpLogs], "dump"),
+            (&[Action::Search], "search"),
+            (&[Action::SwitchTab], "chat"),
+        ]),
     };
 
-    frame.render_widget(
-        Paragraph::new(keybindings)
-            .alignment(Alignment::Left)
-            .cyan()
-            .bold(),
-        bottom_bar_layout[0],
-    );
+    let search = &model.local_search;
+    if search.editing || search.is_active() {
+        let hints = if search.editing {
+            let hints = model.keymap.hints(&[
+                (&[Action::ConfirmSearch], "confirm"),
+                (&[Action::CancelSearch], "cancel"),
+            ]);
+            format!(" ({hints})")
+        } else {
+            let filter = if search.filter { "show all" } else { "filter" };
+            let hints = model.keymap.hints(&[
+                (&[Action::NextMatch, Action::PreviousMatch], "next/prev"),
+                (&[Action::ToggleFilter], filter),
+                (&[Action::Cancel], "clear"),
+            ]);
+            format!(" ({hints})")
+        };
+        let search_line = Line::from(vec![
+            Span::styled(format!("/{}", search.query()), model.theme.search_prompt),
+            Span::styled(hints, model.theme.dim),
+        ]);
+        frame.render_widget(Paragraph::new(search_line), bottom_bar_layout[0]);
+        if search.editing {
+            frame.set_cursor_position(Position::new(
+                bottom_bar_layout[0].x + 1 + search.input.visual_cursor() as u16,
+                bottom_bar_layout[0].y,
+            ));
+        }
+    } else {
+        frame.render_widget(
+            Paragraph::new(keybindings)
+                .alignment(Alignment::Left)
+                .style(model.theme.key_hints),
+            bottom_bar_layout[0],
+        );
+    }
 
     // FPS counter
     frame.render_widget(
         Paragraph::new(format!("FPS: {}", model.fps_counter.fps))
             .alignment(Alignment::Left)
-            .blue(),
+            .style(model.theme.fps),
         bottom_bar_layout[1],
     );
 
@@ -145,69 +246,914 @@ fn render_app_view(frame: &mut Frame<'_>, model: &Model, area: Rect) {
     frame.render_widget(
         Paragraph::new(mode_text)
             .alignment(Alignment::Right)
-            .style(Style::default().add_modifier(Modifier::BOLD)),
+            .style(model.theme.mode),
         bottom_bar_layout[1],
     );
+
+    // The overlays cover the chat, and with it any links under them
+    let popup = centered_rect(main_layout[1], 80, 60);
+    if model.history_search.is_some() || model.link_picker.is_some() {
+        click_areas
+            .links
+            .retain(|(link, _)| !link.intersects(popup));
+    }
+    render_history_search(frame, model, popup);
+    render_link_picker(frame, model, popup);
+}
+
+fn render_history_search(frame: &mut Frame<'_>, model: &Model, area: Rect) {
+    let Some(search) = &model.history_search else {
+        return;
+    };
+
+    let results: Vec<ListItem> = search
+        .results
+        .iter()
+        .map(|message| {
+            let local_time = message.timestamp.with_timezone(&Local);
+            let author = match &message.to {
+                Some(to) => format!("{} → {}", message.author, to),
+                None => message.author.clone(),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{} ", local_time.format("%Y-%m-%d %H:%M")),
+                    model.theme.dim,
+                ),
+                Span::raw(format!("{}: {}", author, message.body.replace('\n', " "))),
+            ]))
+        })
+        .collect();
+    let list = List::new(results)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Search results ({}) | {}",
+                    search.results.len(),
+                    model.keymap.hints(&[
+                        (&[Action::JumpToResult], "jump"),
+                        (&[Action::CloseResults], "close"),
+                    ])
+                ))
+                .style(model.theme.accent),
+        )
+        .highlight_style(model.theme.selected);
+    let mut list_state = ListState::default().with_selected(Some(search.selected));
+
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut list_state);
 }
 
-fn render_chat_view(frame: &mut Frame<'_>, model: &Model, area: Rect) {
+fn render_link_picker(frame: &mut Frame<'_>, model: &Model, area: Rect) {
+    let Some(picker) = &model.link_picker else {
+        return;
+    };
+
+    let items: Vec<ListItem> = picker
+        .links
+        .iter()
+        .enumerate()
+        .map(|(idx, link)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", idx + 1), model.theme.dim),
+                Span::styled(link.as_str(), model.theme.link),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Links | {}",
+                    model.keymap.hints(&[
+                        (&[Action::OpenLink], "open"),
+                        (&[Action::CopyLink], "copy"),
+                        (&[Action::CloseLinks], "close"),
+                    ])
+                ))
+                .style(model.theme.accent),
+        )
+        .highlight_style(model.theme.selected);
+    let mut list_state = ListState::default().with_selected(Some(picker.selected));
+
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
+/// A rectangle of `percent_x` by `percent_y` of `area`, centered in it.
+fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+fn render_chat_view(
+    frame: &mut Frame<'_>,
+    model: &Model,
+    area: Rect,
+    click_areas: &mut ClickAreas,
+) {
     // Chat content layout
+    let typing_users = model.typing.users();
+    let typing_height = u16::from(!typing_users.is_empty());
+    let reply_preview_height = u16::from(model.replying_to.is_some());
+    let input_lines = model.input.value().split('\n').count() as u16;
+    let input_height = input_lines.clamp(1, model.config.input_max_height) + 2;
     let chat_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Percentage(100), // chat content
-            Constraint::Min(3),          // user input
+            Constraint::Percentage(100),              // chat content
+            Constraint::Length(typing_height),        // typing indicator
+            Constraint::Length(reply_preview_height), // reply preview
+            Constraint::Length(input_height),         // user input
         ])
         .split(area);
 
     // messages
-    let messages: Vec<ListItem> = model
-        .messages
+    let entries = model.visible_entries();
+    let chat_width = area.width.saturating_sub(2) as usize;
+    let mut items: Vec<ChatItem> = entries
         .iter()
-        .map(|m| {
-            let content = vec![Line::from(Span::raw(m.to_string()))];
-            ListItem::new(content)
+        .map(|entry| {
+            let (lines, name_row) = chat_entry_lines(model, entry, chat_width);
+            let style = match entry {
+                ChatEntry::Message(message) if model.is_mention(message) => model.theme.mention,
+                _ => Style::default(),
+            };
+            ChatItem {
+                lines,
+                name_row,
+                entry: Some(*entry),
+                style,
+            }
         })
         .collect();
 
+    // "new messages" divider before the first unread message from someone else
+    let divider_idx = model.unread_divider.and_then(|last_read| {
+        entries.iter().position(|entry| {
+            matches!(entry, ChatEntry::Message(message)
+                if message.id > last_read && message.author != model.username)
+        })
+    });
+    if let Some(idx) = divider_idx {
+        let width = area.width.saturating_sub(2) as usize;
+        items.insert(
+            idx,
+            ChatItem {
+                lines: vec![Line::styled(
+                    format!("{:─^width$}", " new messages "),
+                    model.theme.divider,
+                )],
+                name_row: 0,
+                entry: None,
+                style: Style::default(),
+            },
+        );
+    }
+
     let chat_area = chat_layout[0];
 
-    let chat_content = List::new(messages).block(Block::default().borders(Borders::ALL));
+    let mut chat_block = Block::default().borders(Borders::ALL);
+    if model.thread.is_some() {
+        chat_block = chat_block.title(format!(
+            "Thread ({}: close)",
+            model.keymap.key(Action::Cancel)
+        ));
+    }
+    let inner_chat_area = chat_block.inner(chat_area);
+    frame.render_widget(chat_block, chat_area);
+
+    // The first row of each item, and one past the last row of the last item
+    let mut item_rows = vec![0];
+    for item in &items {
+        item_rows.push(item_rows[item_rows.len() - 1] + item.lines.len());
+    }
+    let selected_idx = model.selected.and_then(|selected| {
+        items.iter().position(
+            |item| matches!(item.entry, Some(ChatEntry::Message(message)) if message.id == selected),
+        )
+    });
+    let height = inner_chat_area.height as usize;
+    let top = chat_top_row(
+        item_rows[items.len()],
+        height,
+        model.chat_scroll,
+        selected_idx.map(|idx| item_rows[idx]..item_rows[idx + 1]),
+    );
+    click_areas.chat_rows = (top, item_rows[items.len()].saturating_sub(height));
+
+    // The rows in view, drawn over the style of the item they belong to
+    let row_area = |row: usize| {
+        let y = inner_chat_area.y + (row - top) as u16;
+        Rect::new(inner_chat_area.x, y, inner_chat_area.width, 1)
+    };
+    let bottom = (top + height).min(item_rows[items.len()]);
+    let mut lines = Vec::new();
+    for (idx, item) in items.iter().enumerate() {
+        let (start, end) = (item_rows[idx].max(top), item_rows[idx + 1].min(bottom));
+        for row in start..end {
+            frame.buffer_mut().set_style(row_area(row), item.style);
+            lines.push(item.lines[row - item_rows[idx]].clone());
+        }
+    }
+    frame.render_widget(Paragraph::new(lines), inner_chat_area);
+    if let Some(idx) = selected_idx {
+        for row in item_rows[idx].max(top)..item_rows[idx + 1].min(bottom) {
+            frame
+                .buffer_mut()
+                .set_style(row_area(row), model.theme.selected);
+        }
+    }
+
+    // Usernames and links in view
+    for (idx, item) in items.iter().enumerate() {
+        let Some(entry @ ChatEntry::Message(message)) = item.entry else {
+            continue;
+        };
+        let name_row = item_rows[idx] + item.name_row;
+        let end = item_rows[idx + 1].min(bottom);
+        if name_row < top || name_row >= bottom {
+            continue;
+        }
+        let y = row_area(name_row).y;
+        let x = inner_chat_area.x
+            + timestamp_prefix(model, entry).map_or(0, |prefix| Span::raw(prefix).width() as u16);
+        let width = Span::raw(message.author.as_str()).width() as u16;
+        let name_area = Rect::new(x, y, width, 1).intersection(inner_chat_area);
+        if !name_area.is_empty() {
+            click_areas
+                .usernames
+                .push((name_area, message.author.clone()));
+        }
+
+        let urls: Vec<String> = find_urls(&message.body)
+            .into_iter()
+            .map(|url| message.body[url].to_string())
+            .collect();
+        if !urls.is_empty() {
+            let body_area = Rect::new(
+                inner_chat_area.x,
+                y,
+                inner_chat_area.width,
+                (end - name_row) as u16,
+            );
+            click_areas
+                .links
+                .extend(find_link_areas(frame.buffer_mut(), body_area, &urls));
+        }
+    }
+
+    // Typing indicator
+    let typing_text = match typing_users.as_slice() {
+        [] => String::new(),
+        [user] => format!("{user} is typing…"),
+        [first, second] => format!("{first} and {second} are typing…"),
+        users => format!("{} people are typing…", users.len()),
+    };
+    frame.render_widget(
+        Paragraph::new(typing_text).style(model.theme.dim.add_modifier(Modifier::ITALIC)),
+        chat_layout[1],
+    );
 
-    frame.render_widget(chat_content, chat_area);
+    // Quoted preview of the message being replied to
+    if let Some(id) = model.replying_to {
+        let preview = Line::from(vec![
+            Span::styled("Replying to ", model.theme.dim),
+            Span::raw(quote_snippet(model, id)),
+            Span::styled(
+                format!(" ({}: cancel)", model.keymap.key(Action::StopEditing)),
+                model.theme.dim,
+            ),
+        ]);
+        frame.render_widget(Paragraph::new(preview), chat_layout[2]);
+    }
 
     // Render user input with a border
-    let user_input_area = chat_layout[1];
-    let width = chat_layout[0].width.max(3) - 3;
-    let scroll = model.input.visual_scroll(width as usize);
-    let input_block =
-        Block::default()
-            .borders(Borders::ALL)
-            .style(if model.input_mode == InputMode::Editing {
-                Style::default().fg(Color::Green)
-            } else {
-                Style::default()
-            });
+    let user_input_area = chat_layout[3];
+    const SEARCH_TITLE: &str = "Reverse search: ";
+    let input_title = if let Some(search) = &model.input_history.search {
+        let no_match = search.found.is_none() && !search.query.value().is_empty();
+        format!(
+            "{SEARCH_TITLE}{}{}",
+            search.query.value(),
+            if no_match { " (no match)" } else { "" }
+        )
+    } else if model.editing.is_some() {
+        "Editing message".to_string()
+    } else {
+        String::new()
+    };
+    let input_block = Block::default()
+        .borders(Borders::ALL)
+        .title(input_title)
+        .style(if model.input_mode == InputMode::Editing {
+            model.theme.accent
+        } else {
+            Style::default()
+        });
     frame.render_widget(input_block.clone(), user_input_area);
     let inner_input_area = input_block.inner(user_input_area);
+
+    // Scroll the cursor into view, keeping a column free for it at the end of a line
+    let (cursor_line, cursor_column) = input_cursor(model);
+    let scroll_y = (cursor_line + 1).saturating_sub(inner_input_area.height as usize);
+    let scroll_x = (cursor_column + 1).saturating_sub(inner_input_area.width as usize);
+    click_areas.input = Some((inner_input_area, (scroll_y, scroll_x)));
+
     let user_input = Paragraph::new(model.input.value())
-        .scroll((0, scroll as u16))
+        .scroll((scroll_y as u16, scroll_x as u16))
         .style(match model.input_mode {
             InputMode::Normal => Style::default(),
-            InputMode::Editing => Style::default().fg(Color::Green),
+            InputMode::Editing => model.theme.accent,
         });
     frame.render_widget(user_input, inner_input_area);
 
-    // Set cursor position if in editing mode
-    if let InputMode::Editing = model.input_mode {
+    if let Some(completion) = &model.completion {
+        let (_, word_column) = input_position(model, completion.word_start());
+        let x = inner_input_area.x + word_column.saturating_sub(scroll_x) as u16;
+        let popup = render_completion(frame, model, completion, x, user_input_area.y);
+        click_areas
+            .links
+            .retain(|(link, _)| !link.intersects(popup));
+    }
+
+    // Set cursor position if in editing mode, in the title while searching the input history
+    if let Some(search) = &model.input_history.search {
+        frame.set_cursor_position(Position::new(
+            user_input_area.x + 1 + (SEARCH_TITLE.len() + search.query.visual_cursor()) as u16,
+            user_input_area.y,
+        ));
+    } else if model.input_mode == InputMode::Editing || model.config.vi_mode {
         let cursor_position = Position::new(
-            inner_input_area.x + ((model.input.visual_cursor()).max(scroll) - scroll) as u16,
-            inner_input_area.y);
+            inner_input_area.x + (cursor_column - scroll_x) as u16,
+            inner_input_area.y + (cursor_line - scroll_y) as u16,
+        );
         frame.set_cursor_position(cursor_position);
     }
 }
 
+/// The line and display column of the cursor in the (possibly multi-line) input.
+fn input_cursor(model: &Model) -> (usize, usize) {
+    input_position(model, model.input.cursor())
+}
+
+/// The line and display column of character `idx` of the input.
+fn input_position(model: &Model, idx: usize) -> (usize, usize) {
+    let before: String = model.input.value().chars().take(idx).collect();
+    let line = before.matches('\n').count();
+    let column = Span::raw(before.rsplit('\n').next().unwrap_or_default()).width();
+    (line, column)
+}
+
+/// Popup listing the completion candidates, starting at column `x` just above row `bottom`.
+/// Returns where it was drawn.
+fn render_completion(
+    frame: &mut Frame<'_>,
+    model: &Model,
+    completion: &Completion,
+    x: u16,
+    bottom: u16,
+) -> Rect {
+    const MAX_ROWS: usize = 8;
+    let width = completion
+        .candidates
+        .iter()
+        .map(|candidate| Span::raw(candidate.as_str()).width())
+        .max()
+        .unwrap_or_default() as u16
+        + 2;
+    let height = completion.candidates.len().min(MAX_ROWS) as u16 + 2;
+    let screen = frame.area();
+    let area = Rect::new(
+        x.min(screen.right().saturating_sub(width)),
+        bottom.saturating_sub(height),
+        width,
+        height,
+    )
+    .intersection(screen);
+
+    let items: Vec<ListItem> = completion
+        .candidates
+        .iter()
+        .map(|candidate| ListItem::new(candidate.as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(model.theme.selected);
+    let mut list_state = ListState::default().with_selected(Some(completion.selected));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(list, area, &mut list_state);
+    area
+}
+
+/// The lines of `entry` in the chat view, word wrapped to `width` columns, and the row the
+/// author's name is on.
+/// A chat entry, or the unread divider, as drawn in the chat view.
+struct ChatItem<'a> {
+    lines: Vec<Line<'static>>,
+    /// Row of the author's name, below any reply quote.
+    name_row: usize,
+    /// `None` for the divider.
+    entry: Option<&'a ChatEntry>,
+    /// Drawn under the lines, e.g. to highlight mentions.
+    style: Style,
+}
+
+/// The first row of the chat to show, out of `total` rows, in a view `height` rows tall.
+/// `scroll` is where the view was scrolled to with the mouse. Otherwise the view shows the
+/// newest messages, or ends at the `selected` rows, or starts at them if they don't fit.
+fn chat_top_row(
+    total: usize,
+    height: usize,
+    scroll: Option<usize>,
+    selected: Option<Range<usize>>,
+) -> usize {
+    let max_top = total.saturating_sub(height);
+    match (scroll, selected) {
+        (Some(scroll), _) => scroll.min(max_top),
+        (None, Some(selected)) => selected.end.saturating_sub(height).min(selected.start),
+        (None, None) => max_top,
+    }
+}
+
+/// The lines of a chat entry, wrapped to `width`, and the row of the author's name.
+fn chat_entry_lines(model: &Model, entry: &ChatEntry, width: usize) -> (Vec<Line<'static>>, usize) {
+    let mut lines = Vec::new();
+    if let ChatEntry::Message(ChatMessage {
+        reply_to: Some(parent),
+        ..
+    }) = entry
+    {
+        let quote = Line::styled(
+            format!("  ↪ {}", quote_snippet(model, *parent)),
+            model.theme.dim,
+        );
+        lines.extend(wrap_lines(vec![quote], width, 4));
+    }
+
+    let name_row = lines.len();
+    let mut spans = Vec::new();
+    if let Some(prefix) = timestamp_prefix(model, entry) {
+        spans.push(Span::styled(prefix, model.theme.dim));
+    }
+    // Wrapped lines hang under the start of the body
+    let body_column = |spans: &[Span<'_>]| spans.iter().map(Span::width).sum::<usize>();
+    let mut indent = body_column(&spans);
+    match entry {
+        ChatEntry::Message(message) if message.deleted => {
+            spans.push(Span::styled(
+                message.author.clone(),
+                nick_style(model, &message.author),
+            ));
+            spans.push(Span::raw(": "));
+            indent = body_column(&spans);
+            spans.push(Span::styled(
+                "message deleted",
+                model.theme.dim.add_modifier(Modifier::ITALIC),
+            ));
+        }
+        ChatEntry::Message(message) => {
+            spans.push(Span::styled(
+                message.author.clone(),
+                nick_style(model, &message.author),
+            ));
+            match &message.to {
+                Some(to) => {
+                    spans.push(Span::styled(" → ", model.theme.direct_message));
+                    spans.push(Span::styled(to.clone(), nick_style(model, to)));
+                    spans.push(Span::raw(": "));
+                    indent = body_column(&spans);
+                    spans.extend(message_body(model, message, model.theme.direct_message));
+                }
+                None => {
+                    spans.push(Span::raw(": "));
+                    indent = body_column(&spans);
+                    spans.extend(message_body(model, message, Style::default()));
+                }
+            }
+            if message.edited_at.is_some() {
+                spans.push(Span::styled(" (edited)", model.theme.dim));
+            }
+            if message.offline {
+                spans.push(Span::styled(
+                    " (sent while you were offline)",
+                    model.theme.dim,
+                ));
+            }
+        }
+        ChatEntry::Notice(notice) => {
+            spans.push(Span::styled(notice.text.clone(), model.theme.notice))
+        }
+    }
+    let spans = highlight_matches(spans, model.local_search.query(), model.theme.search_match);
+    lines.extend(wrap_lines(split_lines(spans), width, indent));
+
+    if let ChatEntry::Message(message) = entry {
+        if !message.reactions.is_empty() {
+            let mut spans = vec![Span::raw("   ")];
+            for (emoji, users) in &message.reactions {
+                let style = if users.contains(&model.username) {
+                    model.theme.own_reaction
+                } else {
+                    model.theme.dim
+                };
+                spans.push(Span::styled(format!(" {} {} ", emoji, users.len()), style));
+            }
+            lines.extend(wrap_lines(vec![Line::from(spans)], width, 4));
+        }
+    }
+    (lines, name_row)
+}
+
+/// The body of `message` in `style`, with its markup formatted unless that's turned off.
+fn message_body(model: &Model, message: &ChatMessage, style: Style) -> Vec<Span<'static>> {
+    if model.config.markup && !model.raw_messages.contains(&message.id) {
+        markup(&message.body, style, &model.theme)
+    } else {
+        plain(&message.body, style, &model.theme)
+    }
+}
+
+/// The color of `name` in the chat, with your own name standing out from everyone else's.
+fn nick_style(model: &Model, name: &str) -> Style {
+    if name == model.username {
+        model.theme.own_message
+    } else {
+        model.theme.nick(name)
+    }
+}
+
+/// The local time shown before `entry`, unless timestamps are turned off.
+fn timestamp_prefix(model: &Model, entry: &ChatEntry) -> Option<String> {
+    if model.config.timestamp_format.is_empty() {
+        return None;
+    }
+    let local_time = entry.timestamp().with_timezone(&Local);
+    Some(format!(
+        "{} ",
+        local_time.format(&model.config.timestamp_format)
+    ))
+}
+
+/// A short, single line preview of message `id` for reply quotes.
+fn quote_snippet(model: &Model, id: MessageId) -> String {
+    const MAX_CHARS: usize = 50;
+    match model.find_message(id) {
+        Some(message) if message.deleted => format!("{}: message deleted", message.author),
+        Some(message) => {
+            let first_line = message.body.lines().next().unwrap_or_default();
+            let mut snippet: String = first_line.chars().take(MAX_CHARS).collect();
+            if snippet.len() < message.body.len() {
+                snippet.push('…');
+            }
+            format!("{}: {}", message.author, snippet)
+        }
+        None => "a message that isn't loaded".to_string(),
+    }
+}
+
+/// Breaks `spans` into lines at the line breaks in multi-line messages.
+fn split_lines(spans: Vec<Span<'_>>) -> Vec<Line<'_>> {
+    let mut lines = vec![Line::default()];
+    for span in spans {
+        let mut parts = span.content.split('\n');
+        if let Some(first) = parts.next() {
+            lines
+                .last_mut()
+                .unwrap()
+                .push_span(Span::styled(first.to_string(), span.style));
+        }
+        for part in parts {
+            lines.push(Line::from(Span::styled(part.to_string(), span.style)));
+        }
+    }
+    lines
+}
+
+/// Word wraps `lines` to `width` columns, measuring wide characters and emoji as the terminal
+/// draws them. Every line after the first, from a line break in the text or from wrapping,
+/// is indented by `indent` columns. Words longer than a line are broken anywhere.
+fn wrap_lines(lines: Vec<Line<'_>>, width: usize, indent: usize) -> Vec<Line<'static>> {
+    // Don't indent on screens too narrow to leave room for the text
+    const MIN_TEXT_WIDTH: usize = 10;
+    let indent = if indent + MIN_TEXT_WIDTH > width {
+        0
+    } else {
+        indent
+    };
+    let width = width.max(1);
+
+    let mut wrapped = Vec::new();
+    for line in lines {
+        let mut row = WrapRow::new(if wrapped.is_empty() { 0 } else { indent });
+        let graphemes: Vec<_> = line
+            .spans
+            .iter()
+            .flat_map(|span| span.styled_graphemes(line.style))
+            .collect();
+        // Runs of whitespace and of everything else, each placed as a whole where possible
+        for word in graphemes.chunk_by(|a, b| is_blank(a.symbol) == is_blank(b.symbol)) {
+            let word_width: usize = word.iter().map(|g| g.symbol.width()).sum();
+            if is_blank(word[0].symbol) {
+                if row.width + word_width > width {
+                    // Spaces at a wrap are dropped
+                    wrapped.push(row.finish());
+                    row = WrapRow::wrapped(indent);
+                } else if !row.is_wrap_start() {
+                    row.push_all(word);
+                }
+            } else if row.width + word_width <= width {
+                row.push_all(word);
+            } else if !row.is_empty() && indent + word_width <= width {
+                wrapped.push(row.finish());
+                row = WrapRow::wrapped(indent);
+                row.push_all(word);
+            } else {
+                for grapheme in word {
+                    if row.width + grapheme.symbol.width() > width && !row.is_empty() {
+                        wrapped.push(row.finish());
+                        row = WrapRow::wrapped(indent);
+                    }
+                    row.push(grapheme.symbol, grapheme.style);
+                }
+            }
+        }
+        wrapped.push(row.finish());
+    }
+    wrapped
+}
+
+fn is_blank(symbol: &str) -> bool {
+    symbol.chars().all(char::is_whitespace)
+}
+
+/// A line being filled by `wrap_lines`.
+struct WrapRow {
+    spans: Vec<Span<'static>>,
+    /// Width of the row so far, including the indent.
+    width: usize,
+    indent: usize,
+    /// Whether the row was started by wrapping, rather than by a line break in the text.
+    wrapped: bool,
+}
+
+impl WrapRow {
+    fn new(indent: usize) -> Self {
+        Self {
+            spans: Vec::new(),
+            width: indent,
+            indent,
+            wrapped: false,
+        }
+    }
+
+    fn wrapped(indent: usize) -> Self {
+        Self {
+            wrapped: true,
+            ..Self::new(indent)
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    fn is_wrap_start(&self) -> bool {
+        self.wrapped && self.is_empty()
+    }
+
+    fn push(&mut self, symbol: &str, style: Style) {
+        self.width += symbol.width();
+        match self.spans.last_mut() {
+            Some(last) if last.style == style => last.content.to_mut().push_str(symbol),
+            _ => self.spans.push(Span::styled(symbol.to_string(), style)),
+        }
+    }
+
+    fn push_all(&mut self, graphemes: &[StyledGrapheme<'_>]) {
+        for grapheme in graphemes {
+            self.push(grapheme.symbol, grapheme.style);
+        }
+    }
+
+    fn finish(mut self) -> Line<'static> {
+        // Spaces before a wrap are dropped too
+        while let Some(last) = self.spans.last_mut() {
+            let trimmed = last.content.trim_end().len();
+            if trimmed > 0 {
+                last.content.to_mut().truncate(trimmed);
+                break;
+            }
+            self.spans.pop();
+        }
+        if self.indent > 0 {
+            self.spans.insert(0, Span::raw(" ".repeat(self.indent)));
+        }
+        Line::from(self.spans)
+    }
+}
+
+/// Splits `spans` so the occurrences of `query` stand out.
+fn highlight_matches<'a>(spans: Vec<Span<'a>>, query: &str, highlight: Style) -> Vec<Span<'a>> {
+    if query.is_empty() {
+        return spans;
+    }
+    let mut highlighted = Vec::new();
+    for span in spans {
+        let content = span.content.to_string();
+        let mut last_end = 0;
+        for range in find_matches(&content, query) {
+            if range.start > last_end {
+                highlighted.push(Span::styled(
+                    content[last_end..range.start].to_string(),
+                    span.style,
+                ));
+            }
+            highlighted.push(Span::styled(
+                content[range.clone()].to_string(),
+                span.style.patch(highlight),
+            ));
+            last_end = range.end;
+        }
+        if last_end == 0 {
+            highlighted.push(span);
+        } else if last_end < content.len() {
+            highlighted.push(Span::styled(content[last_end..].to_string(), span.style));
+        }
+    }
+    highlighted
+}
+
+fn render_mentions_view(frame: &mut Frame<'_>, model: &Model, area: Rect) {
+    let mentions = model.mentions();
+    let selected_idx = model.mention_selected.and_then(|selected| {
+        mentions.iter().position(
+            |entry| matches!(entry, ChatEntry::Message(message) if message.id == selected),
+        )
+    });
+    let width = area.width.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = mentions
+        .iter()
+        .map(|entry| ListItem::new(chat_entry_lines(model, entry, width).0))
+        .collect();
+    let mut list_state =
+        ListState::default().with_selected(selected_idx.or(items.len().checked_sub(1)));
+    let mut list = List::new(items).block(Block::default().borders(Borders::ALL).title(format!(
+        "Messages that mention you ({}: show in chat)",
+        model.keymap.key(Action::StartEditing)
+    )));
+    if selected_idx.is_some() {
+        list = list.highlight_style(model.theme.selected);
+    }
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
 fn render_logs_view(frame: &mut Frame<'_>, model: &Model, area: Rect) {
-    let logs = List::new(model.logs.clone()).block(Block::default().borders(Borders::ALL));
-    frame.render_widget(logs, area);
+    let visible_logs = model.visible_logs();
+    let logs: Vec<ListItem> = visible_logs
+        .iter()
+        .map(|idx| {
+            let log = &model.logs[*idx];
+            let mut spans = vec![
+                Span::styled(format!("{} ", log.timestamp), model.theme.dim),
+                Span::styled(
+                    format!("{:<5} ", log.level.as_str()),
+                    model.theme.level(log.level).add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(format!("{}: ", log.context()), model.theme.dim),
+                Span::styled(log.message.clone(), model.theme.level(log.level)),
+            ];
+            if !log.fields.is_empty() {
+                spans.push(Span::styled(
+                    format!(" {}", log.fields_text()),
+                    model.theme.log_fields,
+                ));
+            }
+            ListItem::new(Line::from(highlight_matches(
+                spans,
+                model.local_search.query(),
+                model.theme.search_match,
+            )))
+        })
+        .collect();
+
+    // Keep the selected search match in view, or the newest line when nothing is selected
+    let selected_idx = model
+        .log_selected
+        .and_then(|selected| visible_logs.iter().position(|idx| *idx == selected));
+    let title = Line::from(vec![
+        Span::raw("Minimum level: "),
+        Span::styled(model.log_level.as_str(), model.theme.level(model.log_level)),
+        Span::raw(format!(
+            " ({}: change)",
+            model.keymap.key(Action::CycleLogLevel)
+        )),
+    ]);
+    let mut logs = List::new(logs).block(Block::default().borders(Borders::ALL).title(title));
+    if selected_idx.is_some() {
+        logs = logs.highlight_style(model.theme.selected);
+    }
+    let mut list_state =
+        ListState::default().with_selected(selected_idx.or(visible_logs.len().checked_sub(1)));
+    frame.render_stateful_widget(logs, area, &mut list_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(lines: &[&str], width: usize, indent: usize) -> Vec<String> {
+        let lines = lines.iter().map(|line| Line::raw(*line)).collect();
+        wrap_lines(lines, width, indent)
+            .iter()
+            .map(Line::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn wraps_at_word_boundaries() {
+        assert_eq!(wrap(&["aaa bbb ccc"], 7, 0), ["aaa bbb", "ccc"]);
+        assert_eq!(wrap(&["short"], 20, 0), ["short"]);
+    }
+
+    #[test]
+    fn hangs_wrapped_lines_under_the_body() {
+        assert_eq!(
+            wrap(&["ann: hello world foo bar baz"], 20, 5),
+            ["ann: hello world foo", "     bar baz"]
+        );
+        assert_eq!(wrap(&["ann: a", "b"], 20, 5), ["ann: a", "     b"]);
+    }
+
+    #[test]
+    fn measures_wide_characters() {
+        assert_eq!(wrap(&["你好世界"], 5, 0), ["你好", "世界"]);
+        assert_eq!(wrap(&["😀😀😀"], 4, 0), ["😀😀", "😀"]);
+        assert_eq!(wrap(&["ab 你好"], 5, 0), ["ab", "你好"]);
+    }
+
+    #[test]
+    fn breaks_words_longer_than_a_line() {
+        assert_eq!(wrap(&["abcdefghij"], 4, 0), ["abcd", "efgh", "ij"]);
+        assert_eq!(
+            wrap(&["ann: abcdefghijklmnopqrstuvwxyz"], 16, 5),
+            ["ann: abcdefghijk", "     lmnopqrstuv", "     wxyz"]
+        );
+    }
+
+    #[test]
+    fn drops_the_indent_when_too_narrow() {
+        assert_eq!(wrap(&["ann: abc def"], 8, 5), ["ann: abc", "def"]);
+        assert_eq!(wrap(&["abc"], 0, 0), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn keeps_styles_across_wraps() {
+        let line = Line::from(vec![
+            Span::raw("ann: "),
+            Span::styled("bold words here", Style::new().add_modifier(Modifier::BOLD)),
+        ]);
+        let wrapped = wrap_lines(vec![line], 16, 5);
+        assert_eq!(wrapped[0].to_string(), "ann: bold words");
+        assert_eq!(wrapped[1].to_string(), "     here");
+        assert!(wrapped[1].spans[1]
+            .style
+            .add_modifier
+            .contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn chat_follows_the_newest_rows() {
+        assert_eq!(chat_top_row(30, 10, None, None), 20);
+        assert_eq!(chat_top_row(5, 10, None, None), 0);
+    }
+
+    #[test]
+    fn chat_ends_at_the_selection_or_starts_at_it_if_too_tall() {
+        assert_eq!(chat_top_row(30, 10, None, Some(12..14)), 4);
+        assert_eq!(chat_top_row(30, 10, None, Some(2..4)), 0);
+        assert_eq!(chat_top_row(30, 10, None, Some(5..25)), 5);
+    }
+
+    #[test]
+    fn chat_scrolls_by_row() {
+        assert_eq!(chat_top_row(30, 10, Some(7), Some(12..14)), 7);
+        assert_eq!(chat_top_row(30, 10, Some(25), None), 20);
+    }
 }
@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Unique, monotonically increasing identifier the server assigns to every chat message.
+pub type MessageId = u64;
+
+/// Name of the conversation every user is part of.
+pub const LOBBY: &str = "lobby";
+
+/// Messages sent from a client to the server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Register {
+        username: String,
+    },
+    Chat {
+        body: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reply_to: Option<MessageId>,
+    },
+    /// A private message to a single user, held by the server until they're online.
+    Direct {
+        to: String,
+        body: String,
+    },
+    /// Replace the body of one of the sender's own messages.
+    Edit {
+        id: MessageId,
+        body: String,
+    },
+    /// Delete one of the sender's own messages.
+    Delete {
+        id: MessageId,
+    },
+    /// Add the sender's reaction to a message, or remove it if it's already there.
+    React {
+        id: MessageId,
+        emoji: String,
+    },
+    /// Tells the other users whether the sender is composing a message.
+    Typing {
+        active: bool,
+    },
+    /// Everything in `conversation` up to and including message `id` has been read.
+    MarkRead {
+        conversation: String,
+        id: MessageId,
+    },
+    /// Search the chat history for messages the sender can see.
+    Search(SearchQuery),
+}
+
+/// Filters for a history search. Every filter that is set must match.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchQuery {
+    /// Words that must all appear in the message body, ignoring case.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// [`LOBBY`] for messages to everyone, or a user name for direct messages with that user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<DateTime<Utc>>,
+}
+
+/// Messages sent from the server to its clients.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Answers a `Register`: the name is the client's for as long as it stays connected.
+    Registered,
+    /// Answers a `Register` whose name can't be used, e.g. because it's already connected.
+    /// The client may register again with another name.
+    Rejected {
+        reason: String,
+    },
+    Chat(ChatMessage),
+    Notice(Notice),
+    Edited {
+        id: MessageId,
+        body: String,
+        edited_at: DateTime<Utc>,
+    },
+    Deleted {
+        id: MessageId,
+    },
+    /// The complete, updated set of reactions on a message.
+    Reactions {
+        id: MessageId,
+        reactions: Reactions,
+    },
+    /// Relayed typing state of another user. Never persisted.
+    Typing {
+        user: String,
+        active: bool,
+    },
+    /// The last read message of each conversation, sent to a user when they join.
+    ReadMarkers {
+        markers: HashMap<String, MessageId>,
+    },
+    /// Ends the recent messages sent to a user when they join. Messages after it are new.
+    HistoryEnd,
+    /// The newest matches of a search, oldest first.
+    SearchResults {
+        results: Vec<ChatMessage>,
+    },
+}
+
+/// Most results the server returns for a single search.
+pub const MAX_SEARCH_RESULTS: usize = 100;
+
+/// Reactions on a message, mapping each emoji to the users that reacted with it.
+pub type Reactions = BTreeMap<String, Vec<String>>;
+
+/// Longest emoji, in bytes, the server accepts as a reaction.
+pub const MAX_REACTION_LEN: usize = 32;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub id: MessageId,
+    pub timestamp: DateTime<Utc>,
+    pub author: String,
+    pub body: String,
+    /// Recipient of a direct message, `None` for messages to everyone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Set on direct messages the recipient receives when they join, because they were
+    /// offline when the message was sent.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool,
+    /// The message this one is a reply to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<MessageId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<DateTime<Utc>>,
+    /// Deleted messages keep their place in the history with an empty body.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub reactions: Reactions,
+}
+
+/// A server generated line that isn't authored by any user, e.g. the welcome message.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Notice {
+    pub timestamp: DateTime<Utc>,
+    pub text: String,
+}
+
+impl Notice {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            text: text.into(),
+        }
+    }
+}
+
+/// Encodes a message as a single newline terminated JSON line. Line breaks in message
+/// bodies are escaped by JSON, so multi-line messages still take up exactly one line.
+pub fn encode<T: Serialize>(message: &T) -> Result<String> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// Decodes a single JSON line produced by [`encode`].
+pub fn decode<T: DeserializeOwned>(line: &str) -> Result<T> {
+    Ok(serde_json::from_str(line.trim_end())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(body: &str) -> ChatMessage {
+        ChatMessage {
+            id: 7,
+            timestamp: Utc::now(),
+            author: "ann".to_string(),
+            body: body.to_string(),
+            to: None,
+            offline: false,
+            reply_to: None,
+            edited_at: None,
+            deleted: false,
+            reactions: Reactions::new(),
+        }
+    }
+
+    #[test]
+    fn multi_line_bodies_stay_on_one_line() {
+        let line = encode(&ServerMessage::Chat(message("one\ntwo"))).unwrap();
+        assert_eq!(line.matches('\n').count(), 1);
+        assert!(line.ends_with('\n'));
+        let ServerMessage::Chat(decoded) = decode(&line).unwrap() else {
+            panic!("{line} didn't decode as a chat message");
+        };
+        assert_eq!(decoded.body, "one\ntwo");
+        assert_eq!(decoded.id, 7);
+    }
+
+    #[test]
+    fn leaves_out_unset_fields() {
+        let line = encode(&message("hi")).unwrap();
+        for field in [
+            "to",
+            "offline",
+            "reply_to",
+            "edited_at",
+            "deleted",
+            "reactions",
+        ] {
+            assert!(!line.contains(&format!("\"{field}\"")), "{line}");
+        }
+        let decoded: ChatMessage = decode(&line).unwrap();
+        assert!(decoded.to.is_none() && !decoded.offline && !decoded.deleted);
+    }
+
+    #[test]
+    fn tags_messages_with_their_type() {
+        let line = encode(&ClientMessage::MarkRead {
+            conversation: LOBBY.to_string(),
+            id: 3,
+        })
+        .unwrap();
+        assert_eq!(
+            line,
+            "{\"type\":\"mark_read\",\"conversation\":\"lobby\",\"id\":3}\n"
+        );
+        let query = SearchQuery {
+            text: Some("lunch".to_string()),
+            ..SearchQuery::default()
+        };
+        let ClientMessage::Search(decoded) =
+            decode(&encode(&ClientMessage::Search(query.clone())).unwrap()).unwrap()
+        else {
+            panic!("not a search");
+        };
+        assert_eq!(decoded, query);
+        assert!(matches!(
+            decode(&encode(&ServerMessage::HistoryEnd).unwrap()).unwrap(),
+            ServerMessage::HistoryEnd
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_types() {
+        assert!(decode::<ClientMessage>("{\"type\":\"shout\",\"body\":\"hi\"}").is_err());
+        assert!(decode::<ClientMessage>("not json").is_err());
+    }
+}
@@ -0,0 +1,506 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use protocol::{ChatMessage, MessageId, Reactions, SearchQuery, LOBBY, MAX_SEARCH_RESULTS};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+/// A single change to the chat history. The history file is an append-only log of these,
+/// replayed in order on startup.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Message(ChatMessage),
+    Edit {
+        id: MessageId,
+        body: String,
+        edited_at: DateTime<Utc>,
+    },
+    Delete {
+        id: MessageId,
+    },
+    /// Toggles `user`'s `emoji` reaction on message `id`.
+    React {
+        id: MessageId,
+        emoji: String,
+        user: String,
+    },
+}
+
+/// A record for the writer thread to append, and where to tell whether it was.
+type QueuedWrite = (String, oneshot::Sender<Result<(), String>>);
+
+/// A change to the history that's waiting to be written to the file. It's already made in
+/// memory.
+#[must_use]
+pub struct PendingWrite(oneshot::Receiver<Result<(), String>>);
+
+impl PendingWrite {
+    /// Waits until the change was written, returning why it couldn't be if it wasn't.
+    pub async fn written(self) -> Result<(), String> {
+        self.0
+            .await
+            .unwrap_or_else(|_| Err("The history writer stopped".to_string()))
+    }
+}
+
+/// Every chat message the server has seen, backed by a JSON lines file. The file is written
+/// by a thread of its own, in the order the changes were made, so nobody waits on it while
+/// holding the history.
+pub struct History {
+    messages: Vec<ChatMessage>,
+    writes: mpsc::Sender<QueuedWrite>,
+}
+
+impl History {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open history file {}", path.display()))?;
+        let (writes, queue) = mpsc::channel::<QueuedWrite>();
+        thread::spawn(move || {
+            for (line, written) in queue {
+                let result = file.write_all(line.as_bytes()).and_then(|()| file.flush());
+                let _ = written.send(result.map_err(|e| e.to_string()));
+            }
+        });
+        let mut history = Self {
+            messages: Vec::new(),
+            writes,
+        };
+        let reader = BufReader::new(File::open(path)?);
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = protocol::decode(&line)
+                .with_context(|| format!("Corrupt record in {}: {}", path.display(), line))?;
+            history.apply(record);
+        }
+        Ok(history)
+    }
+
+    /// Stores a new message, assigning it the next id and the current time.
+    pub fn push(
+        &mut self,
+        author: String,
+        body: String,
+        to: Option<String>,
+        reply_to: Option<MessageId>,
+    ) -> (ChatMessage, PendingWrite) {
+        let message = ChatMessage {
+            id: self.messages.last().map_or(1, |last| last.id + 1),
+            timestamp: Utc::now(),
+            author,
+            body,
+            to,
+            offline: false,
+            reply_to,
+            edited_at: None,
+            deleted: false,
+            reactions: Reactions::new(),
+        };
+        let write = self.record(Record::Message(message.clone()));
+        (message, write)
+    }
+
+    pub fn edit(&mut self, id: MessageId, body: String) -> (DateTime<Utc>, PendingWrite) {
+        let edited_at = Utc::now();
+        let write = self.record(Record::Edit {
+            id,
+            body,
+            edited_at,
+        });
+        (edited_at, write)
+    }
+
+    pub fn delete(&mut self, id: MessageId) -> PendingWrite {
+        self.record(Record::Delete { id })
+    }
+
+    /// Toggles a reaction and returns the message's updated reactions.
+    pub fn react(
+        &mut self,
+        id: MessageId,
+        emoji: String,
+        user: String,
+    ) -> (Reactions, PendingWrite) {
+        let write = self.record(Record::React { id, emoji, user });
+        let reactions = self
+            .get(id)
+            .map(|message| message.reactions.clone())
+            .unwrap_or_default();
+        (reactions, write)
+    }
+
+    pub fn get(&self, id: MessageId) -> Option<&ChatMessage> {
+        self.messages
+            .binary_search_by_key(&id, |message| message.id)
+            .ok()
+            .map(|idx| &self.messages[idx])
+    }
+
+    /// Whether `user` is in `conversation`: the lobby, or the name of someone they've
+    /// exchanged direct messages with.
+    pub fn takes_part(&self, user: &str, conversation: &str) -> bool {
+        conversation == LOBBY
+            || self.messages.iter().any(|message| match &message.to {
+                Some(to) => {
+                    (message.author == user && to == conversation)
+                        || (message.author == conversation && to == user)
+                }
+                None => false,
+            })
+    }
+
+    /// The last `count` messages sent to everyone, oldest first.
+    pub fn recent(&self, count: usize) -> Vec<&ChatMessage> {
+        let mut recent: Vec<&ChatMessage> = self
+            .messages
+            .iter()
+            .rev()
+            .filter(|message| message.to.is_none())
+            .take(count)
+            .collect();
+        recent.reverse();
+        recent
+    }
+
+    /// The newest messages visible to `user` that match `query`, oldest first.
+    pub fn search(&self, query: &SearchQuery, user: &str) -> Vec<ChatMessage> {
+        let words: Vec<String> = query
+            .text
+            .iter()
+            .flat_map(|text| text.split_whitespace())
+            .map(str::to_lowercase)
+            .collect();
+        let mut results: Vec<ChatMessage> = self
+            .messages
+            .iter()
+            .rev()
+            .filter(|message| !message.deleted)
+            .filter(|message| match &message.to {
+                Some(to) => *to == user || message.author == user,
+                None => true,
+            })
+            .filter(|message| match query.conversation.as_deref() {
+                None => true,
+                Some(LOBBY) => message.to.is_none(),
+                Some(other) => {
+                    let with = if message.author == user {
+                        message.to.as_deref()
+                    } else {
+                        Some(message.author.as_str())
+                    };
+                    message.to.is_some()
+                        && with.is_some_and(|with| with.eq_ignore_ascii_case(other))
+                }
+            })
+            .filter(|message| {
+                query
+                    .author
+                    .as_ref()
+                    .is_none_or(|author| message.author.eq_ignore_ascii_case(author))
+            })
+            .filter(|message| query.after.is_none_or(|after| message.timestamp >= after))
+            .filter(|message| query.before.is_none_or(|before| message.timestamp < before))
+            .filter(|message| {
+                let body = message.body.to_lowercase();
+                words.iter().all(|word| body.contains(word))
+            })
+            .take(MAX_SEARCH_RESULTS)
+            .cloned()
+            .collect();
+        results.reverse();
+        results
+    }
+
+    /// Makes the change in `record` and queues it to be written.
+    fn record(&mut self, record: Record) -> PendingWrite {
+        let (written, pending) = oneshot::channel();
+        match protocol::encode(&record) {
+            Ok(line) => {
+                // Only fails once the writer thread is gone, which `pending` reports
+                let _ = self.writes.send((line, written));
+            }
+            Err(e) => {
+                let _ = written.send(Err(e.to_string()));
+            }
+        }
+        self.apply(record);
+        PendingWrite(pending)
+    }
+
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Message(message) => self.messages.push(message),
+            Record::Edit {
+                id,
+                body,
+                edited_at,
+            } => {
+                if let Some(message) = self.get_mut(id) {
+                    message.body = body;
+                    message.edited_at = Some(edited_at);
+                }
+            }
+            Record::Delete { id } => {
+                if let Some(message) = self.get_mut(id) {
+                    message.body.clear();
+                    message.deleted = true;
+                    message.reactions.clear();
+                }
+            }
+            Record::React { id, emoji, user } => {
+                if let Some(message) = self.get_mut(id) {
+                    let users = message.reactions.entry(emoji.clone()).or_default();
+                    if let Some(idx) = users.iter().position(|name| *name == user) {
+                        users.remove(idx);
+                        if users.is_empty() {
+                            message.reactions.remove(&emoji);
+                        }
+                    } else {
+                        users.push(user);
+                    }
+                }
+            }
+        }
+    }
+
+    fn get_mut(&mut self, id: MessageId) -> Option<&mut ChatMessage> {
+        self.messages
+            .binary_search_by_key(&id, |message| message.id)
+            .ok()
+            .map(|idx| &mut self.messages[idx])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// A history file of its own for each test, removed if a previous run left it behind.
+    fn history_path(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "chat_history_{}_{}.jsonl",
+            std::process::id(),
+            test
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn push(history: &mut History, author: &str, body: &str, to: Option<&str>) -> PendingWrite {
+        let (_, write) = history.push(
+            author.to_string(),
+            body.to_string(),
+            to.map(str::to_string),
+            None,
+        );
+        write
+    }
+
+    #[tokio::test]
+    async fn assigns_increasing_ids() {
+        let path = history_path("ids");
+        let mut history = History::open(&path).unwrap();
+        let (first, _) = history.push("ann".to_string(), "a".to_string(), None, None);
+        let (second, _) = history.push("bob".to_string(), "b".to_string(), None, Some(1));
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(history.get(2).unwrap().reply_to, Some(1));
+        assert!(history.get(3).is_none());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn replays_changes_from_the_file() {
+        let path = history_path("replay");
+        {
+            let mut history = History::open(&path).unwrap();
+            push(&mut history, "ann", "first", None)
+                .written()
+                .await
+                .unwrap();
+            push(&mut history, "bob", "second", None)
+                .written()
+                .await
+                .unwrap();
+            push(&mut history, "ann", "third", None)
+                .written()
+                .await
+                .unwrap();
+            let (_, write) = history.edit(1, "edited".to_string());
+            write.written().await.unwrap();
+            history.delete(2).written().await.unwrap();
+            let (_, write) = history.react(3, "🎉".to_string(), "bob".to_string());
+            write.written().await.unwrap();
+        }
+
+        let history = History::open(&path).unwrap();
+        let edited = history.get(1).unwrap();
+        assert_eq!(edited.body, "edited");
+        assert!(edited.edited_at.is_some());
+        let deleted = history.get(2).unwrap();
+        assert!(deleted.deleted && deleted.body.is_empty());
+        assert_eq!(history.get(3).unwrap().reactions["🎉"], ["bob"]);
+        let (next, _) =
+            History::open(&path)
+                .unwrap()
+                .push("ann".to_string(), "fourth".to_string(), None, None);
+        assert_eq!(next.id, 4);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn reacting_again_takes_the_reaction_back() {
+        let path = history_path("react");
+        let mut history = History::open(&path).unwrap();
+        let _ = push(&mut history, "ann", "hi", None);
+        let (reactions, _) = history.react(1, "👍".to_string(), "bob".to_string());
+        assert_eq!(reactions["👍"], ["bob"]);
+        let (reactions, _) = history.react(1, "👍".to_string(), "cat".to_string());
+        assert_eq!(reactions["👍"], ["bob", "cat"]);
+        let _ = history.react(1, "👍".to_string(), "bob".to_string());
+        let (reactions, _) = history.react(1, "👍".to_string(), "cat".to_string());
+        assert!(reactions.is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn deleting_clears_the_body_and_reactions() {
+        let path = history_path("delete");
+        let mut history = History::open(&path).unwrap();
+        let _ = push(&mut history, "ann", "hi", None);
+        let _ = history.react(1, "👍".to_string(), "bob".to_string());
+        let _ = history.delete(1);
+        let message = history.get(1).unwrap();
+        assert!(message.deleted && message.body.is_empty() && message.reactions.is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn recent_leaves_out_direct_messages() {
+        let path = history_path("recent");
+        let mut history = History::open(&path).unwrap();
+        let _ = push(&mut history, "ann", "one", None);
+        let _ = push(&mut history, "ann", "secret", Some("bob"));
+        let _ = push(&mut history, "bob", "two", None);
+        let _ = push(&mut history, "bob", "three", None);
+        let bodies = |count| -> Vec<String> {
+            history
+                .recent(count)
+                .into_iter()
+                .map(|message| message.body.clone())
+                .collect()
+        };
+        assert_eq!(bodies(2), ["two", "three"]);
+        assert_eq!(bodies(10), ["one", "two", "three"]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn takes_part_in_the_lobby_and_own_direct_messages() {
+        let path = history_path("takes_part");
+        let mut history = History::open(&path).unwrap();
+        let _ = push(&mut history, "ann", "psst", Some("bob"));
+        assert!(history.takes_part("cat", LOBBY));
+        assert!(history.takes_part("ann", "bob"));
+        assert!(history.takes_part("bob", "ann"));
+        assert!(!history.takes_part("cat", "ann"));
+        assert!(!history.takes_part("ann", "cat"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    fn search(history: &History, query: SearchQuery, user: &str) -> Vec<String> {
+        history
+            .search(&query, user)
+            .into_iter()
+            .map(|message| message.body)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn searches_what_the_user_may_see() {
+        let path = history_path("search");
+        let mut history = History::open(&path).unwrap();
+        let _ = push(&mut history, "ann", "Lunch at noon?", None);
+        let _ = push(&mut history, "bob", "lunch sounds good", Some("ann"));
+        let _ = push(&mut history, "bob", "secret lunch", Some("cat"));
+        let _ = push(&mut history, "cat", "no LUNCH for me", None);
+        let _ = push(&mut history, "ann", "deleted lunch", None);
+        let _ = history.delete(5);
+        let words = |text: &str| SearchQuery {
+            text: Some(text.to_string()),
+            ..SearchQuery::default()
+        };
+
+        assert_eq!(
+            search(&history, words("lunch"), "ann"),
+            ["Lunch at noon?", "lunch sounds good", "no LUNCH for me"]
+        );
+        assert_eq!(
+            search(&history, words("lunch noon"), "ann"),
+            ["Lunch at noon?"]
+        );
+        assert_eq!(
+            search(
+                &history,
+                SearchQuery {
+                    author: Some("BOB".to_string()),
+                    ..words("lunch")
+                },
+                "cat"
+            ),
+            ["secret lunch"]
+        );
+        assert_eq!(
+            search(
+                &history,
+                SearchQuery {
+                    conversation: Some(LOBBY.to_string()),
+                    ..words("lunch")
+                },
+                "ann"
+            ),
+            ["Lunch at noon?", "no LUNCH for me"]
+        );
+        assert_eq!(
+            search(
+                &history,
+                SearchQuery {
+                    conversation: Some("bob".to_string()),
+                    ..SearchQuery::default()
+                },
+                "ann"
+            ),
+            ["lunch sounds good"]
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn search_filters_by_time() {
+        let path = history_path("search_time");
+        let mut history = History::open(&path).unwrap();
+        let _ = push(&mut history, "ann", "hi", None);
+        let sent = history.get(1).unwrap().timestamp;
+        let query = |after, before| SearchQuery {
+            after,
+            before,
+            ..SearchQuery::default()
+        };
+        assert_eq!(search(&history, query(Some(sent), None), "ann"), ["hi"]);
+        assert!(search(&history, query(None, Some(sent)), "ann").is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+}
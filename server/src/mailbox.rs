@@ -0,0 +1,130 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use protocol::MessageId;
+use serde::{Deserialize, Serialize};
+
+/// Every user that ever registered, and the direct messages waiting for the ones that are
+/// offline. Stored as a JSON file.
+pub struct Mailbox {
+    path: PathBuf,
+    state: MailboxState,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct MailboxState {
+    known_users: BTreeSet<String>,
+    /// Undelivered message ids per recipient, oldest first.
+    pending: HashMap<String, Vec<MessageId>>,
+}
+
+impl Mailbox {
+    pub fn open(path: &Path) -> Result<Self> {
+        let state = if path.exists() {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            MailboxState::default()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            state,
+        })
+    }
+
+    pub fn is_known(&self, user: &str) -> bool {
+        self.state.known_users.contains(user)
+    }
+
+    /// Records that `user` registered, returning the messages that are waiting for them. They
+    /// keep waiting until they're marked as `delivered`.
+    pub fn register(&mut self, user: &str) -> Result<Vec<MessageId>> {
+        if self.state.known_users.insert(user.to_string()) {
+            self.save()?;
+        }
+        Ok(self.state.pending.get(user).cloned().unwrap_or_default())
+    }
+
+    /// Stops holding the messages `ids` for `user`, once they were sent to them.
+    pub fn delivered(&mut self, user: &str, ids: &[MessageId]) -> Result<()> {
+        let Some(pending) = self.state.pending.get_mut(user) else {
+            return Ok(());
+        };
+        pending.retain(|id| !ids.contains(id));
+        if pending.is_empty() {
+            self.state.pending.remove(user);
+        }
+        self.save()
+    }
+
+    /// Holds message `id` until `user` next registers.
+    pub fn hold(&mut self, user: &str, id: MessageId) -> Result<()> {
+        self.state
+            .pending
+            .entry(user.to_string())
+            .or_default()
+            .push(id);
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        std::fs::write(&self.path, serde_json::to_string(&self.state)?)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// A mailbox file of its own for each test, removed if a previous run left it behind.
+    fn mailbox_path(test: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("mailbox_{}_{}.json", std::process::id(), test));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn knows_users_once_they_registered() {
+        let path = mailbox_path("known");
+        let mut mailbox = Mailbox::open(&path).unwrap();
+        assert!(!mailbox.is_known("ann"));
+        assert!(mailbox.register("ann").unwrap().is_empty());
+        assert!(mailbox.is_known("ann"));
+        assert!(Mailbox::open(&path).unwrap().is_known("ann"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn holds_messages_until_delivered() {
+        let path = mailbox_path("held");
+        let mut mailbox = Mailbox::open(&path).unwrap();
+        mailbox.register("ann").unwrap();
+        mailbox.hold("ann", 3).unwrap();
+        mailbox.hold("ann", 5).unwrap();
+        mailbox.hold("bob", 4).unwrap();
+
+        // Still waiting when the connection drops before they were sent
+        let mut mailbox = Mailbox::open(&path).unwrap();
+        assert_eq!(mailbox.register("ann").unwrap(), [3, 5]);
+        assert_eq!(mailbox.register("ann").unwrap(), [3, 5]);
+
+        mailbox.hold("ann", 6).unwrap();
+        mailbox.delivered("ann", &[3, 5]).unwrap();
+        assert_eq!(mailbox.register("ann").unwrap(), [6]);
+        mailbox.delivered("ann", &[6]).unwrap();
+        assert!(Mailbox::open(&path)
+            .unwrap()
+            .register("ann")
+            .unwrap()
+            .is_empty());
+        assert_eq!(mailbox.register("bob").unwrap(), [4]);
+        let _ = std::fs::remove_file(path);
+    }
+}
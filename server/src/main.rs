@@ -1,13 +1,33 @@
 use std::collections::HashMap;
-use std::sync::{Arc};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use protocol::{ChatMessage, ClientMessage, MessageId, Notice, ServerMessage, MAX_REACTION_LEN};
+use tokio::sync::Mutex;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader},
     net::TcpListener,
     sync::broadcast,
 };
-use tokio::sync::Mutex;
+
+mod history;
+use history::{History, PendingWrite};
+
+mod read_markers;
+use read_markers::ReadMarkers;
+
+mod mailbox;
+use mailbox::Mailbox;
+
+const HISTORY_PATH: &str = "chat_history.jsonl";
+const READ_MARKERS_PATH: &str = "read_markers.json";
+const MAILBOX_PATH: &str = "mailbox.json";
+/// How often read markers that moved are written to their file.
+const READ_MARKERS_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+/// Number of past messages sent to a client when it joins.
+const HISTORY_ON_JOIN: usize = 50;
 
 #[tokio::main]
 async fn main() {
@@ -24,39 +44,102 @@ struct User {
 
 pub async fn run() -> Result<()> {
     let listener = TcpListener::bind("localhost:8080").await?;
-    let (tx, _rx) = broadcast::channel(15);
+    let (tx, _rx) = broadcast::channel(100);
     let user_map: Arc<Mutex<HashMap<String, User>>> = Arc::new(Mutex::new(HashMap::new()));
+    let history = Arc::new(Mutex::new(History::open(Path::new(HISTORY_PATH))?));
+    let read_markers = Arc::new(Mutex::new(ReadMarkers::open(Path::new(READ_MARKERS_PATH))?));
+    let mailbox = Arc::new(Mutex::new(Mailbox::open(Path::new(MAILBOX_PATH))?));
+    tokio::spawn(read_markers::save_periodically(
+        read_markers.clone(),
+        READ_MARKERS_SAVE_INTERVAL,
+    ));
     println!("Starting server");
     loop {
         let (mut socket, addr) = listener.accept().await?;
 
         let user_map_clone = user_map.clone();
+        let history = history.clone();
+        let read_markers = read_markers.clone();
+        let mailbox = mailbox.clone();
         let tx = tx.clone();
         let mut rx = tx.subscribe();
 
         tokio::spawn(async move {
+            let user_id = addr.to_string();
             let result: Result<()> = async {
                 let (reader, mut writer) = socket.split();
                 let mut reader = BufReader::new(reader);
                 let mut line = String::new();
 
-                let bytes_read = reader.read_line(&mut line).await?;
-                if bytes_read == 0 {
-                    return Ok(());
-                }
-                let username = line.split("username:").collect::<Vec<&str>>()[1]
-                    .trim()
-                    .to_string();
-                let user_id = addr.to_string();
-                let user = User {
-                    name: username.clone(),
-                    _id: user_id.clone(),
+                // Names that are taken are refused, and the client may try another one
+                let username = loop {
+                    line.clear();
+                    if reader.read_line(&mut line).await? == 0 {
+                        return Ok(());
+                    }
+                    let username = match protocol::decode(&line)? {
+                        ClientMessage::Register { username } => username.trim().to_string(),
+                        other => bail!("Expected a register message, got {:?}", other),
+                    };
+                    let rejection = {
+                        let mut user_map = user_map_clone.lock().await;
+                        if username.is_empty() {
+                            Some("Your name can't be empty".to_string())
+                        } else if user_map.values().any(|user| user.name == username) {
+                            Some(format!("{username} is already connected"))
+                        } else {
+                            let user = User {
+                                name: username.clone(),
+                                _id: user_id.clone(),
+                            };
+                            user_map.insert(user_id.clone(), user);
+                            None
+                        }
+                    };
+                    let reply = match rejection {
+                        Some(reason) => ServerMessage::Rejected { reason },
+                        None => ServerMessage::Registered,
+                    };
+                    writer.write_all(protocol::encode(&reply)?.as_bytes()).await?;
+                    if matches!(reply, ServerMessage::Registered) {
+                        break username;
+                    }
                 };
-                user_map_clone.lock().await.insert(user_id.clone(), user);
                 println!("{} connected", username);
 
-                let success_message = format!("Welcome to the chat, {username}!\n", );
-                writer.write_all(success_message.as_bytes()).await?;
+                let welcome = ServerMessage::Notice(Notice::new(format!(
+                    "Welcome to the chat, {username}!"
+                )));
+                writer.write_all(protocol::encode(&welcome)?.as_bytes()).await?;
+                // Markers go first so the client knows where the unread messages start
+                let markers = ServerMessage::ReadMarkers {
+                    markers: read_markers.lock().await.get(&username),
+                };
+                writer.write_all(protocol::encode(&markers)?.as_bytes()).await?;
+                let recent: Vec<ChatMessage> =
+                    history.lock().await.recent(HISTORY_ON_JOIN).into_iter().cloned().collect();
+                for message in recent {
+                    let message = ServerMessage::Chat(message);
+                    writer.write_all(protocol::encode(&message)?.as_bytes()).await?;
+                }
+                let history_end = ServerMessage::HistoryEnd;
+                writer.write_all(protocol::encode(&history_end)?.as_bytes()).await?;
+                // Direct messages sent while this user was offline, in the order they were sent.
+                // They're only dropped from the mailbox once all of them were written.
+                let pending = mailbox.lock().await.register(&username)?;
+                for id in &pending {
+                    let Some(message) = history.lock().await.get(*id).cloned() else {
+                        continue;
+                    };
+                    let message = ServerMessage::Chat(ChatMessage {
+                        offline: true,
+                        ..message
+                    });
+                    writer.write_all(protocol::encode(&message)?.as_bytes()).await?;
+                }
+                if !pending.is_empty() {
+                    mailbox.lock().await.delivered(&username, &pending)?;
+                }
                 line.clear();
 
                 loop {
@@ -65,36 +148,240 @@ pub async fn run() -> Result<()> {
                         if result? == 0 {
                             break;
                         }
-                        if !line.trim().is_empty() {
+                        let client_message = match protocol::decode::<ClientMessage>(&line) {
+                            Ok(client_message) => client_message,
+                            Err(e) => {
+                                eprintln!("Invalid message from {}: {}", user_id, e);
+                                line.clear();
+                                continue;
+                            }
+                        };
+                        line.clear();
 
-                            let user_name = {
-                                let user_map_guard = user_map_clone.lock().await;
+                        let user_name = {
+                            let user_map_guard = user_map_clone.lock().await;
 
-                                if let Some(user) = user_map_guard.get(&user_id) {
-                                    user.name.clone()
-                                } else {
-                                    eprintln!("User not found for ID: {}", user_id);
+                            if let Some(user) = user_map_guard.get(&user_id) {
+                                user.name.clone()
+                            } else {
+                                eprintln!("User not found for ID: {}", user_id);
+                                continue;
+                            }
+                        };
+
+                        match client_message {
+                            ClientMessage::Chat { body, reply_to } => {
+                                if body.trim().is_empty() {
                                     continue;
                                 }
-                            };
-                            let msg = format!("{}: {}", user_name, line.clone());
-                            tx.send((msg, addr))?;
+                                // The lock is let go of before writing to the socket
+                                let result = {
+                                    let mut history = history.lock().await;
+                                    match reply_to.filter(|id| history.get(*id).is_none()) {
+                                        Some(parent) => Err(format!("Message {parent} does not exist")),
+                                        None => {
+                                            let (msg, write) = history.push(user_name, body, None, reply_to);
+                                            tx.send((ServerMessage::Chat(msg), addr))?;
+                                            Ok(write)
+                                        }
+                                    }
+                                };
+                                match result {
+                                    Ok(write) => report_write(write, &mut writer).await?,
+                                    Err(reason) => {
+                                        let notice = ServerMessage::Notice(Notice::new(reason));
+                                        writer.write_all(protocol::encode(&notice)?.as_bytes()).await?;
+                                    }
+                                }
+                            }
+                            ClientMessage::Direct { to, body } => {
+                                let to = to.trim().to_string();
+                                if body.trim().is_empty() {
+                                    continue;
+                                }
+                                let held = {
+                                    let mut mailbox = mailbox.lock().await;
+                                    if !mailbox.is_known(&to) {
+                                        Err(format!("Unknown user {to}"))
+                                    } else {
+                                        let is_online = user_map_clone
+                                            .lock()
+                                            .await
+                                            .values()
+                                            .any(|user| user.name == to);
+                                        let (msg, write) = history.lock().await.push(user_name, body, Some(to.clone()), None);
+                                        if is_online {
+                                            tx.send((ServerMessage::Chat(msg), addr))?;
+                                            Ok((None, write))
+                                        } else {
+                                            mailbox.hold(&to, msg.id)?;
+                                            Ok((Some(msg), write))
+                                        }
+                                    }
+                                };
+                                match held {
+                                    Ok((None, write)) => report_write(write, &mut writer).await?,
+                                    Ok((Some(msg), write)) => {
+                                        report_write(write, &mut writer).await?;
+                                        writer.write_all(protocol::encode(&ServerMessage::Chat(msg))?.as_bytes()).await?;
+                                        let notice = ServerMessage::Notice(Notice::new(format!(
+                                            "{to} is offline and will get your message when they next join"
+                                        )));
+                                        writer.write_all(protocol::encode(&notice)?.as_bytes()).await?;
+                                    }
+                                    Err(reason) => {
+                                        let notice = ServerMessage::Notice(Notice::new(reason));
+                                        writer.write_all(protocol::encode(&notice)?.as_bytes()).await?;
+                                    }
+                                }
+                            }
+                            ClientMessage::Edit { id, body } => {
+                                if body.trim().is_empty() {
+                                    continue;
+                                }
+                                let result = {
+                                    let mut history = history.lock().await;
+                                    match authorize_change(&history, id, &user_name) {
+                                        Ok(()) => {
+                                            let (edited_at, write) = history.edit(id, body.clone());
+                                            tx.send((ServerMessage::Edited { id, body, edited_at }, addr))?;
+                                            Ok(write)
+                                        }
+                                        Err(reason) => Err(reason),
+                                    }
+                                };
+                                match result {
+                                    Ok(write) => report_write(write, &mut writer).await?,
+                                    Err(reason) => {
+                                        let notice = ServerMessage::Notice(Notice::new(reason));
+                                        writer.write_all(protocol::encode(&notice)?.as_bytes()).await?;
+                                    }
+                                }
+                            }
+                            ClientMessage::Delete { id } => {
+                                let result = {
+                                    let mut history = history.lock().await;
+                                    match authorize_change(&history, id, &user_name) {
+                                        Ok(()) => {
+                                            let write = history.delete(id);
+                                            tx.send((ServerMessage::Deleted { id }, addr))?;
+                                            Ok(write)
+                                        }
+                                        Err(reason) => Err(reason),
+                                    }
+                                };
+                                match result {
+                                    Ok(write) => report_write(write, &mut writer).await?,
+                                    Err(reason) => {
+                                        let notice = ServerMessage::Notice(Notice::new(reason));
+                                        writer.write_all(protocol::encode(&notice)?.as_bytes()).await?;
+                                    }
+                                }
+                            }
+                            ClientMessage::React { id, emoji } => {
+                                let emoji = emoji.trim().to_string();
+                                if emoji.is_empty() || emoji.len() > MAX_REACTION_LEN {
+                                    continue;
+                                }
+                                let result = {
+                                    let mut history = history.lock().await;
+                                    match history.get(id) {
+                                        Some(message) if message.to.is_some() => {
+                                            Err("You can't react to direct messages".to_string())
+                                        }
+                                        Some(message) if !message.deleted => {
+                                            let (reactions, write) = history.react(id, emoji, user_name);
+                                            tx.send((ServerMessage::Reactions { id, reactions }, addr))?;
+                                            Ok(write)
+                                        }
+                                        _ => Err(format!("Message {id} does not exist")),
+                                    }
+                                };
+                                match result {
+                                    Ok(write) => report_write(write, &mut writer).await?,
+                                    Err(reason) => {
+                                        let notice = ServerMessage::Notice(Notice::new(reason));
+                                        writer.write_all(protocol::encode(&notice)?.as_bytes()).await?;
+                                    }
+                                }
+                            }
+                            ClientMessage::Typing { active } => {
+                                tx.send((ServerMessage::Typing { user: user_name, active }, addr))?;
+                            }
+                            ClientMessage::MarkRead { conversation, id } => {
+                                // Markers for anything else would only fill up the file
+                                let takes_part = history.lock().await.takes_part(&user_name, &conversation);
+                                if takes_part {
+                                    read_markers.lock().await.mark_read(&user_name, conversation, id);
+                                } else {
+                                    eprintln!("{} marked {} read, which they aren't in", user_id, conversation);
+                                }
+                            }
+                            ClientMessage::Search(query) => {
+                                let results = history.lock().await.search(&query, &user_name);
+                                let msg = ServerMessage::SearchResults { results };
+                                writer.write_all(protocol::encode(&msg)?.as_bytes()).await?;
+                            }
+                            ClientMessage::Register { .. } => {
+                                eprintln!("{} tried to register twice", user_id);
+                            }
                         }
-                        line.clear();
                     },
                     result = rx.recv() => {
-                        let (msg, _other_addr) = result?;
+                        let (msg, other_addr) = result?;
+                        if other_addr == addr && matches!(msg, ServerMessage::Typing { .. }) {
+                            continue;
+                        }
+                        // Direct messages only go to the two users involved
+                        if let ServerMessage::Chat(ChatMessage { to: Some(to), author, .. }) = &msg {
+                            if *to != username && *author != username {
+                                continue;
+                            }
+                        }
 
-                        writer.write_all(msg.as_bytes()).await?;
+                        writer.write_all(protocol::encode(&msg)?.as_bytes()).await?;
 
                     },
                 }
                 }
                 Ok(())
             }.await;
+            user_map_clone.lock().await.remove(&user_id);
             if let Err(e) = result {
                 eprintln!("Error handling connection: {:?}", e);
             }
         });
     }
 }
+
+/// Tells the user when `write` didn't make it into the history file. The change was made
+/// and sent to everyone already, it's only lost once the server restarts, so their
+/// connection carries on.
+async fn report_write(write: PendingWrite, writer: &mut (impl AsyncWrite + Unpin)) -> Result<()> {
+    if let Err(e) = write.written().await {
+        eprintln!("Failed to write {}: {}", HISTORY_PATH, e);
+        let notice = ServerMessage::Notice(Notice::new(
+            "The server couldn't save your change, it will be lost when the server restarts",
+        ));
+        writer
+            .write_all(protocol::encode(&notice)?.as_bytes())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Checks that `user_name` is allowed to edit or delete message `id`. Users are only told
+/// apart by the name they registered with, so this trusts that name.
+fn authorize_change(history: &History, id: MessageId, user_name: &str) -> Result<(), String> {
+    match history.get(id) {
+        None => Err(format!("Message {id} does not exist")),
+        Some(message) if message.deleted => Err(format!("Message {id} was deleted")),
+        Some(message) if message.to.is_some() => {
+            Err("Direct messages can't be changed".to_string())
+        }
+        Some(message) if message.author != user_name => {
+            Err("You can only change your own messages".to_string())
+        }
+        Some(_) => Ok(()),
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use protocol::MessageId;
+use tokio::sync::Mutex;
+
+/// The last message each user has read in each conversation, stored as a JSON file.
+pub struct ReadMarkers {
+    path: PathBuf,
+    markers: HashMap<String, HashMap<String, MessageId>>,
+    /// Set when markers moved since the file was last written.
+    changed: bool,
+}
+
+impl ReadMarkers {
+    pub fn open(path: &Path) -> Result<Self> {
+        let markers = if path.exists() {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            markers,
+            changed: false,
+        })
+    }
+
+    pub fn get(&self, user: &str) -> HashMap<String, MessageId> {
+        self.markers.get(user).cloned().unwrap_or_default()
+    }
+
+    /// Moves `user`'s marker in `conversation` forward to `id`. Markers never move backwards,
+    /// so a client that's behind can't undo what another client already read.
+    pub fn mark_read(&mut self, user: &str, conversation: String, id: MessageId) {
+        let marker = self
+            .markers
+            .entry(user.to_string())
+            .or_default()
+            .entry(conversation)
+            .or_default();
+        if id > *marker {
+            *marker = id;
+            self.changed = true;
+        }
+    }
+}
+
+/// Writes the markers to their file every `interval` if they moved. They move with nearly
+/// every message read, so they're written in batches, and only the markers read in the
+/// last `interval` are lost when the server stops.
+pub async fn save_periodically(read_markers: Arc<Mutex<ReadMarkers>>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let (path, contents) = {
+            let mut read_markers = read_markers.lock().await;
+            if !std::mem::take(&mut read_markers.changed) {
+                continue;
+            }
+            let contents = serde_json::to_string(&read_markers.markers);
+            (read_markers.path.clone(), contents)
+        };
+        let written = match contents {
+            Ok(contents) => {
+                let path = path.clone();
+                match tokio::task::spawn_blocking(move || std::fs::write(path, contents)).await {
+                    Ok(result) => result.map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = written {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            // Tried again next time
+            read_markers.lock().await.changed = true;
+        }
+    }
+}